@@ -0,0 +1,425 @@
+//! Assembler for the mnemonics of the instruction quick reference.
+//!
+//! A source file holds one statement per line, `;` starting a comment:
+//!
+//! ```text
+//! SPEED equ 4             ; a constant
+//! include "sprites.asm"   ; the statements of another file, in place
+//!
+//! start:                  ; a label, naming the address of what follows
+//!     LD I, ball
+//!     DRW V0, V1, 4
+//!     ADD V0, SPEED
+//!     JP start
+//! ball:
+//!     db 0x60, 0xf0, 0xf0, 0x60
+//!     dw 0x1234, ball + 2
+//! ```
+//!
+//! Mnemonics, registers and directives are case insensitive, labels and
+//! constants are not. Numbers are decimal, or hex and binary with a `0x` or
+//! `0b` prefix, and operands may add or subtract numbers, labels and
+//! constants. The program is assembled to run at 0x200, or at the address of
+//! an `org` directive before its first label or statement (`org 0x600`).
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use error::Chip8Error;
+use interpreter::INTERPRETER_END;
+
+/// How deeply includes and constants referring to constants may nest
+const MAX_NESTING: usize = 32;
+
+/// Assembles source text. Included files are looked up relative to the
+/// current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, Chip8Error> {
+    let mut asm = Assembler::new();
+    asm.read(source, None)?;
+    asm.emit()
+}
+
+/// Assembles a source file. Included files are looked up relative to the
+/// directory of the file that includes them.
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    let mut asm = Assembler::new();
+    let source = read_source(path, None)?;
+    asm.read(&source, Some(path))?;
+    asm.emit()
+}
+
+/// Reads a source file, reporting failures at the include in `location`
+fn read_source(path: &Path, location: Option<&Location>) -> Result<String, Chip8Error> {
+    let mut source = String::new();
+    let result = File::open(path).and_then(|mut file| file.read_to_string(&mut source));
+    match (result, location) {
+        (Ok(_), _) => Ok(source),
+        (Err(e), Some(location)) => {
+            Err(location.error(format!("could not include {}: {}", path.display(), e)))
+        },
+        (Err(e), None) => Err(Chip8Error::Io(e)),
+    }
+}
+
+/// Where a statement came from
+#[derive(Clone)]
+struct Location {
+    file: Option<PathBuf>,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, message: String) -> Chip8Error {
+        Chip8Error::Assemble { file: self.file.clone(), line: self.line, message }
+    }
+}
+
+enum Symbol {
+    Label(i64),
+    Constant(String),
+}
+
+enum Statement {
+    Instruction(String, Vec<String>),
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+}
+
+/// An instruction operand, expressions are kept as text until every label
+/// is known
+enum Operand {
+    V(u8),
+    Range(u8, u8),
+    I,
+    IndirectI,
+    Long(String),
+    DT,
+    ST,
+    K,
+    F,
+    HF,
+    B,
+    R,
+    Expr(String),
+}
+
+impl Operand {
+    fn parse(text: &str) -> Operand {
+        let upper = text.to_uppercase();
+        match upper.as_str() {
+            "I" => return Operand::I,
+            "[I]" => return Operand::IndirectI,
+            "DT" => return Operand::DT,
+            "ST" => return Operand::ST,
+            "K" => return Operand::K,
+            "F" => return Operand::F,
+            "HF" => return Operand::HF,
+            "B" => return Operand::B,
+            "R" => return Operand::R,
+            _ => {},
+        }
+        if let Some(x) = register(text) {
+            return Operand::V(x);
+        }
+        if upper.starts_with("LONG ") {
+            return Operand::Long(text[5..].trim().to_string());
+        }
+        let mut ends = text.splitn(2, '-').map(register);
+        if let (Some(Some(x)), Some(Some(y))) = (ends.next(), ends.next()) {
+            return Operand::Range(x, y);
+        }
+        Operand::Expr(text.to_string())
+    }
+}
+
+/// Parses `V0` - `VF`
+fn register(text: &str) -> Option<u8> {
+    let text = text.trim();
+    if text.len() == 2 && (text.starts_with('V') || text.starts_with('v')) {
+        u8::from_str_radix(&text[1..], 16).ok()
+    } else {
+        None
+    }
+}
+
+fn is_symbol_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {},
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') &&
+        matches!(Operand::parse(name), Operand::Expr(_))
+}
+
+struct Assembler {
+    symbols: HashMap<String, Symbol>,
+    statements: Vec<(Location, Statement)>,
+    /// Address of the next statement
+    next_address: i64,
+    includes: Vec<PathBuf>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Assembler {
+            symbols: HashMap::new(),
+            statements: Vec::new(),
+            next_address: INTERPRETER_END as i64,
+            includes: Vec::new(),
+        }
+    }
+
+    /// First pass: collects the statements and the address of every label
+    fn read(&mut self, source: &str, file: Option<&Path>) -> Result<(), Chip8Error> {
+        for (idx, line) in source.lines().enumerate() {
+            let location = Location { file: file.map(Path::to_path_buf), line: idx + 1 };
+            let mut text = line.split(';').next().unwrap_or("").trim();
+
+            if let Some(colon) = text.find(':') {
+                let name = text[..colon].trim();
+                if is_symbol_name(name) {
+                    let address = self.next_address;
+                    self.define(name, Symbol::Label(address), &location)?;
+                    text = text[colon + 1..].trim();
+                }
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let mut words = text.splitn(2, char::is_whitespace);
+            let head = words.next().unwrap_or("");
+            let tail = words.next().unwrap_or("").trim();
+            let mut tail_words = tail.splitn(2, char::is_whitespace);
+            if tail_words.next().is_some_and(|w| w.eq_ignore_ascii_case("equ")) {
+                if !is_symbol_name(head) {
+                    return Err(location.error(format!("invalid constant name '{}'", head)));
+                }
+                let value = tail_words.next().unwrap_or("").trim().to_string();
+                self.define(head, Symbol::Constant(value), &location)?;
+                continue;
+            }
+
+            let operands: Vec<String> = if tail.is_empty() {
+                Vec::new()
+            } else {
+                tail.split(',').map(|op| op.trim().to_string()).collect()
+            };
+            let statement = match head.to_lowercase().as_str() {
+                "org" => {
+                    let labelled = self.symbols.values().any(|symbol| matches!(symbol, Symbol::Label(_)));
+                    if labelled || !self.statements.is_empty() {
+                        return Err(location.error("org has to come before any label or statement".to_string()));
+                    }
+                    self.next_address = self.value(tail, 0, 0xffff, 0)
+                        .map_err(|message| location.error(message))?;
+                    continue;
+                },
+                "include" => {
+                    self.include(tail.trim_matches('"'), file, &location)?;
+                    continue;
+                },
+                "db" => {
+                    self.next_address += operands.len() as i64;
+                    Statement::Bytes(operands)
+                },
+                "dw" => {
+                    self.next_address += 2 * operands.len() as i64;
+                    Statement::Words(operands)
+                },
+                _ => {
+                    let long = operands.len() == 2 && operands[1].to_uppercase().starts_with("LONG ");
+                    self.next_address += if long { 4 } else { 2 };
+                    Statement::Instruction(head.to_uppercase(), operands)
+                },
+            };
+            self.statements.push((location, statement));
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, name: &str, symbol: Symbol, location: &Location) -> Result<(), Chip8Error> {
+        if self.symbols.contains_key(name) {
+            return Err(location.error(format!("'{}' is already defined", name)));
+        }
+        self.symbols.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    fn include(&mut self, name: &str, from: Option<&Path>, location: &Location) -> Result<(), Chip8Error> {
+        let path = match from.and_then(Path::parent) {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        };
+        if self.includes.len() >= MAX_NESTING || self.includes.contains(&path) {
+            return Err(location.error(format!("{} includes itself", path.display())));
+        }
+        let source = read_source(&path, Some(location))?;
+        self.includes.push(path.clone());
+        let result = self.read(&source, Some(&path));
+        self.includes.pop();
+        result
+    }
+
+    /// Second pass: encodes the statements now that all labels are known
+    fn emit(&self) -> Result<Vec<u8>, Chip8Error> {
+        let mut program = Vec::new();
+        for (location, statement) in &self.statements {
+            let result = match *statement {
+                Statement::Bytes(ref values) => values.iter()
+                    .map(|value| self.value(value, -128, 0xff, 0).map(|v| v as u8))
+                    .collect(),
+                Statement::Words(ref values) => values.iter()
+                    .map(|value| self.value(value, -0x8000, 0xffff, 0).map(|v| v as u16))
+                    .collect::<Result<Vec<u16>, String>>()
+                    .map(|words| to_bytes(&words)),
+                Statement::Instruction(ref mnemonic, ref operands) => {
+                    self.encode(mnemonic, operands).map(|words| to_bytes(&words))
+                },
+            };
+            program.extend(result.map_err(|message| location.error(message))?);
+        }
+        Ok(program)
+    }
+
+    fn encode(&self, mnemonic: &str, operands: &[String]) -> Result<Vec<u16>, String> {
+        use self::Operand::*;
+        use instruction::Instruction as Ins;
+
+        let ops: Vec<Operand> = operands.iter().map(|op| Operand::parse(op)).collect();
+        let instruction = match (mnemonic, ops.as_slice()) {
+            ("CLS", []) => Ins::Cls,
+            ("RET", []) => Ins::Ret,
+            ("SCR", []) => Ins::Scr,
+            ("SCL", []) => Ins::Scl,
+            ("EXIT", []) => Ins::Exit,
+            ("LOW", []) => Ins::Low,
+            ("HIGH", []) => Ins::High,
+            ("AUDIO", []) => Ins::Audio,
+            ("SYS", [Expr(a)]) => Ins::Sys(self.address(a)?),
+            ("SCD", [Expr(n)]) => Ins::Scd(self.nibble(n)?),
+            ("SCU", [Expr(n)]) => Ins::Scu(self.nibble(n)?),
+            ("JP", [Expr(a)]) => Ins::Jp(self.address(a)?),
+            ("JP", [V(0), Expr(a)]) => Ins::JpV0(self.address(a)?),
+            ("CALL", [Expr(a)]) => Ins::Call(self.address(a)?),
+            ("SE", [V(x), Expr(kk)]) => Ins::SeByte(*x, self.byte(kk)?),
+            ("SNE", [V(x), Expr(kk)]) => Ins::SneByte(*x, self.byte(kk)?),
+            ("SE", [V(x), V(y)]) => Ins::SeReg(*x, *y),
+            ("LD", [IndirectI, Range(x, y)]) => Ins::LdMemRange(*x, *y),
+            ("LD", [Range(x, y), IndirectI]) => Ins::LdRangeMem(*x, *y),
+            ("LD", [V(x), Expr(kk)]) => Ins::LdByte(*x, self.byte(kk)?),
+            ("ADD", [V(x), Expr(kk)]) => Ins::AddByte(*x, self.byte(kk)?),
+            ("LD", [V(x), V(y)]) => Ins::LdReg(*x, *y),
+            ("OR", [V(x), V(y)]) => Ins::Or(*x, *y),
+            ("AND", [V(x), V(y)]) => Ins::And(*x, *y),
+            ("XOR", [V(x), V(y)]) => Ins::Xor(*x, *y),
+            ("ADD", [V(x), V(y)]) => Ins::AddReg(*x, *y),
+            ("SUB", [V(x), V(y)]) => Ins::Sub(*x, *y),
+            // y = x shifts Vx whether or not the quirks shift Vy
+            ("SHR", [V(x)]) => Ins::Shr(*x, *x),
+            ("SHR", [V(x), V(y)]) => Ins::Shr(*x, *y),
+            ("SUBN", [V(x), V(y)]) => Ins::Subn(*x, *y),
+            ("SHL", [V(x)]) => Ins::Shl(*x, *x),
+            ("SHL", [V(x), V(y)]) => Ins::Shl(*x, *y),
+            ("SNE", [V(x), V(y)]) => Ins::SneReg(*x, *y),
+            ("LD", [I, Expr(a)]) => Ins::LdI(self.address(a)?),
+            ("LD", [I, Long(a)]) => {
+                return Ok(vec![Ins::LdILong.encode(), self.value(a, 0, 0xffff, 0)? as u16]);
+            },
+            ("RND", [V(x), Expr(kk)]) => Ins::Rnd(*x, self.byte(kk)?),
+            ("DRW", [V(x), V(y), Expr(n)]) => Ins::Drw(*x, *y, self.nibble(n)?),
+            ("SKP", [V(x)]) => Ins::Skp(*x),
+            ("SKNP", [V(x)]) => Ins::Sknp(*x),
+            ("PLANE", [Expr(n)]) => Ins::Plane(self.nibble(n)?),
+            ("LD", [V(x), DT]) => Ins::LdVxDt(*x),
+            ("LD", [V(x), K]) => Ins::LdVxK(*x),
+            ("LD", [DT, V(x)]) => Ins::LdDtVx(*x),
+            ("LD", [ST, V(x)]) => Ins::LdStVx(*x),
+            ("ADD", [I, V(x)]) => Ins::AddI(*x),
+            ("LD", [F, V(x)]) => Ins::LdF(*x),
+            ("LD", [HF, V(x)]) => Ins::LdHf(*x),
+            ("LD", [B, V(x)]) => Ins::LdB(*x),
+            ("PITCH", [V(x)]) => Ins::Pitch(*x),
+            ("LD", [IndirectI, V(x)]) => Ins::LdMemVx(*x),
+            ("LD", [V(x), IndirectI]) => Ins::LdVxMem(*x),
+            ("LD", [R, V(x)]) => Ins::LdRVx(*x),
+            ("LD", [V(x), R]) => Ins::LdVxR(*x),
+            _ if operands.is_empty() => return Err(format!("invalid instruction '{}'", mnemonic)),
+            _ => return Err(format!("invalid instruction '{} {}'", mnemonic, operands.join(", "))),
+        };
+        Ok(vec![instruction.encode()])
+    }
+
+    fn address(&self, expr: &str) -> Result<u16, String> {
+        self.value(expr, 0, 0xfff, 0).map(|v| v as u16)
+    }
+
+    fn byte(&self, expr: &str) -> Result<u8, String> {
+        self.value(expr, -128, 0xff, 0).map(|v| v as u8)
+    }
+
+    fn nibble(&self, expr: &str) -> Result<u8, String> {
+        self.value(expr, 0, 0xf, 0).map(|v| v as u8)
+    }
+
+    /// Evaluates an expression and checks that it lies in `min..=max`
+    fn value(&self, expr: &str, min: i64, max: i64, depth: usize) -> Result<i64, String> {
+        let value = self.eval(expr, depth)?;
+        if value < min || value > max {
+            return Err(format!("'{}' is out of range ({} to {})", expr, min, max));
+        }
+        Ok(value)
+    }
+
+    /// Evaluates a sum of numbers, labels and constants
+    fn eval(&self, expr: &str, depth: usize) -> Result<i64, String> {
+        if depth > MAX_NESTING {
+            return Err(format!("'{}' is defined in terms of itself", expr));
+        }
+        let mut total = 0;
+        let mut sign = 1;
+        let mut rest = expr.trim();
+        if rest.is_empty() {
+            return Err("missing value".to_string());
+        }
+        if rest.starts_with('-') {
+            sign = -1;
+            rest = rest[1..].trim_start();
+        }
+        loop {
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            total += sign * self.term(rest[..end].trim(), depth)?;
+            if end == rest.len() {
+                return Ok(total);
+            }
+            sign = if rest[end..].starts_with('+') { 1 } else { -1 };
+            rest = &rest[end + 1..];
+        }
+    }
+
+    fn term(&self, term: &str, depth: usize) -> Result<i64, String> {
+        let lower = term.to_lowercase();
+        let number = if lower.starts_with("0x") {
+            i64::from_str_radix(&term[2..], 16).ok()
+        } else if lower.starts_with("0b") {
+            i64::from_str_radix(&term[2..], 2).ok()
+        } else {
+            term.parse().ok()
+        };
+        if let Some(number) = number {
+            return Ok(number);
+        }
+        match self.symbols.get(term) {
+            Some(Symbol::Label(address)) => Ok(*address),
+            Some(Symbol::Constant(value)) => self.eval(value, depth + 1),
+            None if term.is_empty() => Err("missing value".to_string()),
+            None => Err(format!("unknown label or constant '{}'", term)),
+        }
+    }
+}
+
+fn to_bytes(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|w| vec![(w >> 8) as u8, *w as u8]).collect()
+}
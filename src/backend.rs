@@ -0,0 +1,196 @@
+//! Traits a frontend implements to show the screen, play the tone and feed
+//! the hex keypad, along with implementations that need no host devices.
+
+use std::collections::VecDeque;
+use error::Chip8Error;
+use framebuffer::Framebuffer;
+use interpreter::{KEY_COUNT, AUDIO_PATTERN_LENGTH};
+
+/// Presents the chip-8 framebuffer
+pub trait Display {
+    /// Shows the framebuffer, called only when its contents changed
+    fn present(&mut self, framebuffer: &Framebuffer);
+
+    /// The failures since the last call that didn't stop the display, such
+    /// as a frame it could not draw. Called once per frame, see
+    /// `Runner::take_errors`.
+    fn take_errors(&mut self) -> Vec<Chip8Error> {
+        Vec::new()
+    }
+}
+
+/// Plays the chip-8 tone
+pub trait Audio {
+    /// Starts (`true`) or stops (`false`) the tone. Called once per frame.
+    fn set_tone(&mut self, on: bool);
+
+    /// Replaces the tone with an XO-CHIP audio pattern: 128 one bit samples
+    /// played at 4000 * 2 ^ ((pitch - 64) / 48) samples per second. Called
+    /// whenever the program changes the pattern or pitch.
+    fn set_pattern(&mut self, _pattern: &[u8; AUDIO_PATTERN_LENGTH], _pitch: u8) {}
+}
+
+/// Requests from the host to the frontend, as opposed to keypad input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Stop running
+    Quit,
+    /// Restart the loaded program
+    Reset,
+    /// Save the machine to the numbered slot
+    SaveState(u8),
+    /// Restore the machine from the numbered slot
+    LoadState(u8),
+    /// Go back one frame instead of running the next one, sent every frame
+    /// the rewind key is held
+    Rewind,
+}
+
+/// Source of the 16-key hex keypad state
+pub trait Input {
+    /// Gathers pending host input, called once per frame before the keypad
+    /// is queried. Returns the commands requested since the last poll.
+    fn poll(&mut self) -> Vec<Command>;
+
+    /// Returns true if the hex key (0x0 - 0xf) is held down
+    fn is_pressed(&self, key: u8) -> bool;
+
+    /// The failures since the last call that didn't stop the input, such as
+    /// a controller that could not be opened. Called once per frame, see
+    /// `Runner::take_errors`.
+    fn take_errors(&mut self) -> Vec<Chip8Error> {
+        Vec::new()
+    }
+}
+
+/// A display that shows nothing
+#[derive(Default)]
+pub struct NullDisplay;
+
+impl Display for NullDisplay {
+    fn present(&mut self, _framebuffer: &Framebuffer) {}
+}
+
+/// An audio backend that plays nothing
+#[derive(Default)]
+pub struct NullAudio;
+
+impl Audio for NullAudio {
+    fn set_tone(&mut self, _on: bool) {}
+}
+
+/// An input source with no key ever pressed
+#[derive(Default)]
+pub struct NullInput;
+
+impl Input for NullInput {
+    fn poll(&mut self) -> Vec<Command> {
+        Vec::new()
+    }
+
+    fn is_pressed(&self, _key: u8) -> bool {
+        false
+    }
+}
+
+/// A display that keeps a copy of every presented frame
+#[derive(Default)]
+pub struct RecordingDisplay {
+    frames: Vec<Vec<u8>>,
+}
+
+impl RecordingDisplay {
+    /// The presented frames, oldest first
+    pub fn frames(&self) -> &[Vec<u8>] {
+        &self.frames
+    }
+}
+
+impl Display for RecordingDisplay {
+    fn present(&mut self, framebuffer: &Framebuffer) {
+        self.frames.push(framebuffer.pixels().to_vec());
+    }
+}
+
+/// An audio backend that records when the tone was switched on and off and
+/// which audio patterns were set
+#[derive(Default)]
+pub struct RecordingAudio {
+    frame: usize,
+    tone: bool,
+    changes: Vec<(usize, bool)>,
+    patterns: Vec<(usize, [u8; AUDIO_PATTERN_LENGTH], u8)>,
+}
+
+impl RecordingAudio {
+    /// The (frame, tone on) pairs at which the tone changed state
+    pub fn changes(&self) -> &[(usize, bool)] {
+        &self.changes
+    }
+
+    /// The (frame, pattern, pitch) triples at which the audio pattern changed
+    pub fn patterns(&self) -> &[(usize, [u8; AUDIO_PATTERN_LENGTH], u8)] {
+        &self.patterns
+    }
+}
+
+impl Audio for RecordingAudio {
+    fn set_tone(&mut self, on: bool) {
+        if on != self.tone {
+            self.tone = on;
+            self.changes.push((self.frame, on));
+        }
+        self.frame += 1;
+    }
+
+    fn set_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_LENGTH], pitch: u8) {
+        self.patterns.push((self.frame, *pattern, pitch));
+    }
+}
+
+/// An input source driven by the host program. Keypad states queued with
+/// `push_frame` are consumed one per poll; once the queue is empty the last
+/// state is held.
+#[derive(Default)]
+pub struct ScriptedInput {
+    keys: u16,
+    script: VecDeque<u16>,
+    commands: Vec<Command>,
+}
+
+impl ScriptedInput {
+    /// Presses or releases a key until the next scripted frame changes it
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if key >= KEY_COUNT {
+            return;
+        }
+        if pressed {
+            self.keys |= 1 << key;
+        } else {
+            self.keys &= !(1 << key);
+        }
+    }
+
+    /// Queues the keypad state for a frame, bit n set meaning key n is held
+    pub fn push_frame(&mut self, keys: u16) {
+        self.script.push_back(keys);
+    }
+
+    /// Queues a command to be returned by the next poll
+    pub fn push_command(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+}
+
+impl Input for ScriptedInput {
+    fn poll(&mut self) -> Vec<Command> {
+        if let Some(keys) = self.script.pop_front() {
+            self.keys = keys;
+        }
+        self.commands.drain(..).collect()
+    }
+
+    fn is_pressed(&self, key: u8) -> bool {
+        key < KEY_COUNT && self.keys & (1 << key) != 0
+    }
+}
@@ -1,24 +1,36 @@
-extern crate rand;
-
-use self::rand::ThreadRng;
-use self::rand::Rng;
+use rng::RandomSource;
 
 pub struct Cpu {
     pub registers: Reg,
-    rng: ThreadRng,
+    rng: Box<dyn RandomSource>,
 }
 
 impl Cpu {
 
-    pub fn init() -> Self {
+    pub fn init(rng: Box<dyn RandomSource>) -> Self {
         Cpu {
             registers: Reg::default(),
-            rng: rand::thread_rng(),
+            rng,
         }
     }
     /// Generates a random byte
     pub fn random_byte(&mut self) -> u8 {
-        self.rng.gen_range(::std::u8::MIN, ::std::u8::MAX)
+        self.rng.next_byte()
+    }
+
+    /// The source of random bytes
+    pub fn random_source(&self) -> &dyn RandomSource {
+        &*self.rng
+    }
+
+    /// Mutable access to the source of random bytes
+    pub fn random_source_mut(&mut self) -> &mut dyn RandomSource {
+        &mut *self.rng
+    }
+
+    /// Replaces the source of random bytes
+    pub fn set_random_source(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
     }
 }
 
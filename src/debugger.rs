@@ -0,0 +1,483 @@
+//! An interactive debugger: a command prompt that stops and starts the
+//! program, inspects and changes the machine and manages breakpoints,
+//! watchpoints and conditions.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, Write};
+use backend::{Display, Audio, Input};
+use disasm::mnemonic;
+use error::Chip8Error;
+use instruction::Instruction;
+use interpreter::{Interpreter, Pause};
+use runner::{Runner, FRAME_RATE};
+use watch::{parse_address, parse_number, Condition, Register, Watchpoint};
+
+const PROMPT: &str = "(pschip8) ";
+/// Bytes `mem` shows unless given a length
+const DEFAULT_DUMP_LENGTH: usize = 64;
+/// Instructions `disasm` shows unless given a count
+const DEFAULT_DISASM_COUNT: usize = 10;
+/// Instructions `disasm` shows before the program counter
+const DISASM_CONTEXT: u16 = 3;
+
+static HELP: &str = "\
+break ADDR           (b)   stop before the instruction at ADDR runs
+delete [ADDR]              remove the breakpoint at ADDR, or every breakpoint
+breakpoints          (bl)  list the breakpoints
+watch ADDR [END]           stop after an instruction writes to ADDR (through END)
+rwatch ADDR [END]          stop after an instruction reads ADDR, sprites included
+awatch ADDR [END]          stop after an instruction reads or writes ADDR
+xwatch ADDR [END]          stop after an instruction is fetched from ADDR
+cond REG OP VALUE          stop when a comparison turns true, e.g. 'cond VF == 1' or 'cond I > 0xE00'
+watches                    list the watchpoints and conditions
+unwatch N                  remove watchpoint N
+uncond N                   remove condition N
+step [N]             (s)   run N instructions, 1 by default, the timers count down once
+                           every frame's worth of instructions
+continue [N]         (c)   run until something stops it, the program exits, the window closes
+                           or N frames (60 a second) went by
+regs                 (r)   show the registers and timers
+mem ADDR [LEN]       (x)   dump LEN bytes of memory starting at ADDR
+write ADDR BYTE...   (w)   store bytes in memory starting at ADDR
+set REG VALUE              set V0-VF, I, PC, DT or ST
+disasm [ADDR] [N]    (l)   disassemble N instructions, around the program counter by default
+stack                (bt)  show the call stack
+reset                      restart the program, breakpoints are kept
+help                 (h)   show this list
+quit                 (q)   leave the debugger
+An empty line repeats the last command. Numbers are decimal or hex with a 0x prefix.
+";
+
+/// Reads commands and runs the program through a `Runner`, so it works the
+/// same with the SDL window open or with the null backends.
+#[derive(Default)]
+pub struct Debugger {
+    /// Repeated when the user enters an empty line
+    last_command: Option<String>,
+    /// Frames `continue` runs at most unless told otherwise
+    frame_limit: Option<usize>,
+    /// `continue` runs the frames as fast as possible instead of in real time
+    headless: bool,
+    /// Instructions `step` ran since the timers last counted down
+    stepped: usize,
+}
+
+impl Debugger {
+    /// Creates a debugger
+    pub fn new() -> Self {
+        Debugger::default()
+    }
+
+    /// Makes `continue` stop after `frames` frames when nothing stops it
+    /// sooner, None lets it run until something does. Without a window
+    /// nothing can interrupt the program, so it needs a limit.
+    pub fn set_frame_limit(&mut self, frames: Option<usize>) {
+        self.frame_limit = frames;
+    }
+
+    /// Makes `continue` run the frames as fast as possible instead of in
+    /// real time, for sessions without a window where nobody watches the
+    /// program
+    pub fn set_headless(&mut self, headless: bool) {
+        self.headless = headless;
+    }
+
+    /// Prompts for commands on `output` and executes them until `quit`, the
+    /// end of `input` or the window is closed
+    pub fn run<D, A, I, R, W>(&mut self, runner: &mut Runner<D, A, I>, intp: &mut Interpreter,
+                              mut input: R, mut output: W) -> Result<(), Chip8Error>
+        where D: Display, A: Audio, I: Input, R: BufRead, W: Write
+    {
+        runner.refresh(intp);
+        writeln!(output, "{}", location(intp))?;
+        loop {
+            write!(output, "{}", PROMPT)?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                return Ok(());
+            }
+            let line = match (line.trim(), self.last_command.take()) {
+                ("", Some(last)) => last,
+                (line, _) => line.to_string(),
+            };
+            if !self.execute(runner, intp, &line, &mut output)? {
+                return Ok(());
+            }
+            if !line.is_empty() {
+                self.last_command = Some(line);
+            }
+        }
+    }
+
+    /// Executes one command line and writes what it shows to `output`.
+    /// Mistakes in the command and errors of the program are reported on
+    /// `output` too. Returns false if the session is over.
+    pub fn execute<D, A, I, W>(&mut self, runner: &mut Runner<D, A, I>, intp: &mut Interpreter,
+                               line: &str, output: &mut W) -> Result<bool, Chip8Error>
+        where D: Display, A: Audio, I: Input, W: Write
+    {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Ok(true),
+        };
+        let mut text = String::new();
+        let result = match name {
+            "break" | "b" => set_breakpoint(intp, args, &mut text),
+            "delete" => delete_breakpoint(intp, args, &mut text),
+            "breakpoints" | "bl" => list_breakpoints(intp, &mut text),
+            "watch" => add_watchpoint(intp, args, "write", &mut text),
+            "rwatch" => add_watchpoint(intp, args, "read", &mut text),
+            "awatch" => add_watchpoint(intp, args, "read/write", &mut text),
+            "xwatch" => add_watchpoint(intp, args, "fetch", &mut text),
+            "cond" => add_condition(intp, args, &mut text),
+            "watches" => list_watches(intp, &mut text),
+            "unwatch" => remove_watchpoint(intp, args, &mut text),
+            "uncond" => remove_condition(intp, args, &mut text),
+            "step" | "s" => step(runner, intp, args, &mut self.stepped, &mut text),
+            "continue" | "c" => {
+                // the frames count the timers down themselves
+                self.stepped = 0;
+                resume(runner, intp, args, self.frame_limit, self.headless, &mut text)
+            },
+            "regs" | "r" => registers(intp, &mut text),
+            "mem" | "x" => dump_memory(intp, args, &mut text),
+            "write" | "w" => write_memory(intp, args, &mut text),
+            "set" => set_register(intp, args, &mut text),
+            "disasm" | "l" => disassemble(intp, args, &mut text),
+            "stack" | "bt" => call_stack(intp, &mut text),
+            "reset" => {
+                self.stepped = 0;
+                intp.reset();
+                runner.refresh(intp);
+                line_to(&mut text, &location(intp));
+                Ok(true)
+            },
+            "help" | "h" => {
+                text.push_str(HELP);
+                Ok(true)
+            },
+            "quit" | "q" => Ok(false),
+            _ => Err(format!("unknown command '{}', try 'help'", name)),
+        };
+        output.write_all(text.as_bytes())?;
+        match result {
+            Ok(running) => Ok(running),
+            Err(message) => {
+                writeln!(output, "error: {}", message)?;
+                Ok(true)
+            },
+        }
+    }
+}
+
+type CommandResult = Result<bool, String>;
+
+fn set_breakpoint(intp: &mut Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let address = parse_address(one_arg(args, "an address")?)?;
+    intp.add_breakpoint(address);
+    line_to(text, &format!("breakpoint at 0x{:03X}", address));
+    Ok(true)
+}
+
+fn delete_breakpoint(intp: &mut Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    if args.is_empty() {
+        intp.clear_breakpoints();
+        line_to(text, "deleted every breakpoint");
+        return Ok(true);
+    }
+    let address = parse_address(one_arg(args, "an address")?)?;
+    if !intp.remove_breakpoint(address) {
+        return Err(format!("no breakpoint at 0x{:03X}", address));
+    }
+    line_to(text, &format!("deleted the breakpoint at 0x{:03X}", address));
+    Ok(true)
+}
+
+fn list_breakpoints(intp: &Interpreter, text: &mut String) -> CommandResult {
+    if intp.breakpoints().is_empty() {
+        line_to(text, "no breakpoints");
+    }
+    for address in intp.breakpoints() {
+        line_to(text, &format!("0x{:03X}: {}", address, instruction_at(intp, *address)));
+    }
+    Ok(true)
+}
+
+/// Adds a watchpoint for `accesses`, written as `Watchpoint` parses them
+fn add_watchpoint(intp: &mut Interpreter, args: &[&str], accesses: &str, text: &mut String) -> CommandResult {
+    let range = match args {
+        [start] => start.to_string(),
+        [start, end] => format!("{}-{}", start, end),
+        _ => return Err("expected an address and an optional end address".to_string()),
+    };
+    let watchpoint: Watchpoint = format!("{} {}", accesses, range).parse()?;
+    intp.add_watchpoint(watchpoint);
+    line_to(text, &format!("watchpoint {}: {}", intp.watchpoints().len() - 1, watchpoint));
+    Ok(true)
+}
+
+fn add_condition(intp: &mut Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let condition: Condition = args.join(" ").parse()?;
+    intp.add_condition(condition);
+    line_to(text, &format!("condition {}: {}", intp.conditions().len() - 1, condition));
+    Ok(true)
+}
+
+fn list_watches(intp: &Interpreter, text: &mut String) -> CommandResult {
+    if intp.watchpoints().is_empty() && intp.conditions().is_empty() {
+        line_to(text, "no watchpoints or conditions");
+    }
+    for (index, watchpoint) in intp.watchpoints().iter().enumerate() {
+        line_to(text, &format!("watchpoint {}: {}", index, watchpoint));
+    }
+    for (index, condition) in intp.conditions().iter().enumerate() {
+        line_to(text, &format!("condition {}: {}", index, condition));
+    }
+    Ok(true)
+}
+
+fn remove_watchpoint(intp: &mut Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let index = parse_number(one_arg(args, "a watchpoint number")?)?;
+    let watchpoint = intp.remove_watchpoint(index).ok_or_else(|| format!("no watchpoint {}", index))?;
+    line_to(text, &format!("deleted watchpoint {}: {}", index, watchpoint));
+    Ok(true)
+}
+
+fn remove_condition(intp: &mut Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let index = parse_number(one_arg(args, "a condition number")?)?;
+    let condition = intp.remove_condition(index).ok_or_else(|| format!("no condition {}", index))?;
+    line_to(text, &format!("deleted condition {}: {}", index, condition));
+    Ok(true)
+}
+
+/// Runs instructions one by one. `stepped` counts them so that the timers
+/// count down after each frame's worth at the runner's clock, as they would
+/// while the program runs, and delay loops end.
+fn step<D, A, I>(runner: &mut Runner<D, A, I>, intp: &mut Interpreter, args: &[&str],
+                 stepped: &mut usize, text: &mut String) -> CommandResult
+    where D: Display, A: Audio, I: Input
+{
+    let count = match args {
+        [] => 1,
+        [count] => parse_number(count)?,
+        _ => return Err("expected at most one count".to_string()),
+    };
+    let frame_cycles = ::std::cmp::max(runner.cpu_hz() / FRAME_RATE, 1) as usize;
+    let mut result = Ok(());
+    for _ in 0..count {
+        result = intp.step();
+        *stepped += 1;
+        if *stepped >= frame_cycles {
+            intp.tick_timers();
+            *stepped = 0;
+        }
+        if result.is_err() || intp.pause().is_some() || intp.is_halted() {
+            break;
+        }
+    }
+    runner.refresh(intp);
+    result.map_err(|e| e.to_string())?;
+    stopped(intp, text);
+    Ok(true)
+}
+
+fn resume<D, A, I>(runner: &mut Runner<D, A, I>, intp: &mut Interpreter, args: &[&str],
+                   limit: Option<usize>, headless: bool, text: &mut String) -> CommandResult
+    where D: Display, A: Audio, I: Input
+{
+    let limit = match args {
+        [] => limit,
+        [frames] => Some(parse_number(frames)?),
+        _ => return Err("expected at most one frame count".to_string()),
+    };
+    if intp.is_halted() {
+        return Err("the program has exited, 'reset' restarts it".to_string());
+    }
+    let frames = limit.unwrap_or(usize::MAX);
+    let ran = if headless { runner.run_frames(intp, frames) } else { runner.run_for(intp, frames) };
+    let ran = ran.map_err(|e| e.to_string())?;
+    if ran == frames {
+        line_to(text, &format!("stopped after {} frames", frames));
+    } else if intp.pause().is_none() && !intp.is_halted() {
+        // the window was closed
+        return Ok(false);
+    }
+    stopped(intp, text);
+    Ok(true)
+}
+
+/// Tells why the program stopped and where
+fn stopped(intp: &Interpreter, text: &mut String) {
+    match intp.pause() {
+        Some(Pause::Breakpoint(address)) => {
+            line_to(text, &format!("breakpoint at 0x{:03X}", address));
+        },
+        Some(Pause::Watchpoint { pc, address, access }) => {
+            line_to(text, &format!("watchpoint: {} of 0x{:03X} by the instruction at 0x{:03X}",
+                                   access, address, pc));
+        },
+        Some(Pause::Condition(condition)) => {
+            line_to(text, &format!("condition: {}", condition));
+        },
+        None => {},
+    }
+    if intp.is_halted() {
+        line_to(text, "the program exited");
+    } else {
+        if intp.is_waiting_for_key() {
+            line_to(text, "waiting for a key");
+        }
+        line_to(text, &location(intp));
+    }
+}
+
+fn registers(intp: &Interpreter, text: &mut String) -> CommandResult {
+    for row in 0..2 {
+        let values: Vec<String> = (row * 8..row * 8 + 8)
+            .map(|x| format!("V{:X}=0x{:02X}", x, intp.register(x).unwrap()))
+            .collect();
+        line_to(text, &values.join(" "));
+    }
+    line_to(text, &format!("I=0x{:03X} PC=0x{:03X} SP={} DT={} ST={}",
+                           intp.index(), intp.pc(), intp.call_stack().len(),
+                           intp.delay_timer(), intp.sound_timer()));
+    Ok(true)
+}
+
+fn dump_memory(intp: &Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let (start, len) = match args {
+        [start] => (parse_address(start)? as usize, DEFAULT_DUMP_LENGTH),
+        [start, len] => (parse_address(start)? as usize, parse_number(len)?),
+        _ => return Err("expected an address and an optional length".to_string()),
+    };
+    let memory = intp.memory();
+    if start >= memory.len() {
+        return Err(format!("0x{:03X} is outside the memory", start));
+    }
+    let end = ::std::cmp::min(start.saturating_add(len), memory.len());
+    for (row, bytes) in memory[start..end].chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        line_to(text, &format!("0x{:03X}: {}", start + row * 16, hex.join(" ")));
+    }
+    Ok(true)
+}
+
+fn write_memory(intp: &mut Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let (start, bytes) = match args.split_first() {
+        Some((start, bytes)) if !bytes.is_empty() => (parse_address(start)? as usize, bytes),
+        _ => return Err("expected an address and at least one byte".to_string()),
+    };
+    let bytes = bytes.iter().map(|byte| parse_byte(byte)).collect::<Result<Vec<u8>, String>>()?;
+    let memory = intp.memory_mut();
+    if start + bytes.len() > memory.len() {
+        return Err("the bytes don't fit in the memory".to_string());
+    }
+    memory[start..start + bytes.len()].copy_from_slice(&bytes);
+    line_to(text, &format!("wrote {} byte(s) at 0x{:03X}", bytes.len(), start));
+    Ok(true)
+}
+
+fn set_register(intp: &mut Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let (register, value) = match args {
+        [name, value] => (name.parse::<Register>()?, *value),
+        _ => return Err("expected a register and a value".to_string()),
+    };
+    match register {
+        Register::V(x) => intp.set_register(x, parse_byte(value)?),
+        Register::I => intp.set_index(parse_address(value)?),
+        Register::Pc => intp.set_pc(parse_address(value)?),
+        Register::Dt => intp.set_delay_timer(parse_byte(value)?),
+        Register::St => intp.set_sound_timer(parse_byte(value)?),
+        Register::Sp => return Err("SP can't be set".to_string()),
+    }
+    line_to(text, &format!("{} = {}", register, value));
+    Ok(true)
+}
+
+fn disassemble(intp: &Interpreter, args: &[&str], text: &mut String) -> CommandResult {
+    let (start, count) = match args {
+        [] => (intp.pc().saturating_sub(DISASM_CONTEXT * 2), DEFAULT_DISASM_COUNT),
+        [start] => (parse_address(start)?, DEFAULT_DISASM_COUNT),
+        [start, count] => (parse_address(start)?, parse_number(count)?),
+        _ => return Err("expected an optional address and count".to_string()),
+    };
+    let mut address = start as usize;
+    for _ in 0..count {
+        if address + 1 >= intp.memory().len() {
+            break;
+        }
+        let current = if address == intp.pc() as usize { "=>" } else { "  " };
+        let breakpoint = if intp.breakpoints().contains(&(address as u16)) { "*" } else { " " };
+        line_to(text, &format!("{}{} 0x{:03X}: {}", current, breakpoint, address,
+                               instruction_at(intp, address as u16)));
+        address += width_at(intp, address as u16) as usize;
+    }
+    Ok(true)
+}
+
+fn call_stack(intp: &Interpreter, text: &mut String) -> CommandResult {
+    let stack = intp.call_stack();
+    line_to(text, &format!("#0 0x{:03X}: {}", intp.pc(), instruction_at(intp, intp.pc())));
+    // each entry is the address after the CALL that pushed it
+    for (depth, address) in stack.iter().rev().enumerate() {
+        let call = address.wrapping_sub(2);
+        line_to(text, &format!("#{} 0x{:03X}: {}", depth + 1, call, instruction_at(intp, call)));
+    }
+    Ok(true)
+}
+
+/// The program counter and the instruction it points at
+fn location(intp: &Interpreter) -> String {
+    format!("0x{:03X}: {}", intp.pc(), instruction_at(intp, intp.pc()))
+}
+
+/// The opcode and mnemonic of the instruction at `address`
+fn instruction_at(intp: &Interpreter, address: u16) -> String {
+    let (opcode, next) = match (word_at(intp, address), word_at(intp, address.wrapping_add(2))) {
+        (Some(opcode), next) => (opcode, next),
+        (None, _) => return "outside the memory".to_string(),
+    };
+    match mnemonic(opcode, next) {
+        Some(text) => format!("{:04X}  {}", opcode, text),
+        None => format!("{:04X}  dw 0x{:04X}", opcode, opcode),
+    }
+}
+
+/// Bytes taken by the instruction at `address`
+fn width_at(intp: &Interpreter, address: u16) -> u16 {
+    word_at(intp, address)
+        .and_then(|opcode| Instruction::decode(opcode).ok())
+        .map_or(2, |instruction| instruction.width())
+}
+
+fn word_at(intp: &Interpreter, address: u16) -> Option<u16> {
+    let memory = intp.memory();
+    let address = address as usize;
+    if address + 1 < memory.len() {
+        Some(((memory[address] as u16) << 8) | memory[address + 1] as u16)
+    } else {
+        None
+    }
+}
+
+fn line_to(text: &mut String, line: &str) {
+    let _ = writeln!(text, "{}", line);
+}
+
+fn one_arg<'a>(args: &[&'a str], what: &str) -> Result<&'a str, String> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err(format!("expected {}", what)),
+    }
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let value = parse_number(text)?;
+    if value > 0xff {
+        return Err(format!("'{}' does not fit in a byte", text));
+    }
+    Ok(value as u8)
+}
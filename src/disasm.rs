@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+use instruction::Instruction;
+use interpreter::INTERPRETER_END;
+
+/// Column at which the address and opcode comment of a line starts
+const COMMENT_COLUMN: usize = 28;
+
+/// Returns the mnemonic for a single instruction, in the style of the
+/// instruction quick reference (e.g. `DRW V1, V2, 5` or `LD I, 0x2A0`),
+/// or None if the opcode is not part of the instruction set.
+///
+/// The XO-CHIP `F000 nnnn` instruction is two words long, its second word is
+/// passed as `next`.
+pub fn mnemonic(opcode: u16, next: Option<u16>) -> Option<String> {
+    mnemonic_with_labels(opcode, next, &|addr| format!("0x{:03X}", addr))
+}
+
+/// Builds the mnemonic, formatting jump and call targets with `target`
+fn mnemonic_with_labels(opcode: u16, next: Option<u16>, target: &dyn Fn(u16) -> String) -> Option<String> {
+    use instruction::Instruction::*;
+
+    let text = match Instruction::decode(opcode).ok()? {
+        Sys(nnn) => format!("SYS 0x{:03X}", nnn),
+        Cls => "CLS".to_string(),
+        Ret => "RET".to_string(),
+        Jp(nnn) => format!("JP {}", target(nnn)),
+        Call(nnn) => format!("CALL {}", target(nnn)),
+        SeByte(x, kk) => format!("SE V{:X}, 0x{:02X}", x, kk),
+        SneByte(x, kk) => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        SeReg(x, y) => format!("SE V{:X}, V{:X}", x, y),
+        LdByte(x, kk) => format!("LD V{:X}, 0x{:02X}", x, kk),
+        AddByte(x, kk) => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        LdReg(x, y) => format!("LD V{:X}, V{:X}", x, y),
+        Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+        And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+        Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+        AddReg(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+        Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+        Shr(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+        Subn(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+        Shl(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+        SneReg(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+        LdI(nnn) => format!("LD I, 0x{:03X}", nnn),
+        JpV0(nnn) => format!("JP V0, 0x{:03X}", nnn),
+        Rnd(x, kk) => format!("RND V{:X}, 0x{:02X}", x, kk),
+        Drw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Skp(x) => format!("SKP V{:X}", x),
+        Sknp(x) => format!("SKNP V{:X}", x),
+        LdVxDt(x) => format!("LD V{:X}, DT", x),
+        LdVxK(x) => format!("LD V{:X}, K", x),
+        LdDtVx(x) => format!("LD DT, V{:X}", x),
+        LdStVx(x) => format!("LD ST, V{:X}", x),
+        AddI(x) => format!("ADD I, V{:X}", x),
+        LdF(x) => format!("LD F, V{:X}", x),
+        LdB(x) => format!("LD B, V{:X}", x),
+        LdMemVx(x) => format!("LD [I], V{:X}", x),
+        LdVxMem(x) => format!("LD V{:X}, [I]", x),
+        Scd(n) => format!("SCD {}", n),
+        Scr => "SCR".to_string(),
+        Scl => "SCL".to_string(),
+        Exit => "EXIT".to_string(),
+        Low => "LOW".to_string(),
+        High => "HIGH".to_string(),
+        LdHf(x) => format!("LD HF, V{:X}", x),
+        LdRVx(x) => format!("LD R, V{:X}", x),
+        LdVxR(x) => format!("LD V{:X}, R", x),
+        Scu(n) => format!("SCU {}", n),
+        LdMemRange(x, y) => format!("LD [I], V{:X} - V{:X}", x, y),
+        LdRangeMem(x, y) => format!("LD V{:X} - V{:X}, [I]", x, y),
+        LdILong => format!("LD I, long 0x{:04X}", next?),
+        Plane(n) => format!("PLANE {}", n),
+        Audio => "AUDIO".to_string(),
+        Pitch(x) => format!("PITCH V{:X}", x),
+    };
+    Some(text)
+}
+
+/// Name of the label placed at a jump or call target
+fn label(addr: u16) -> String {
+    format!("L{:03X}", addr)
+}
+
+/// A decoded word (or two, for F000 nnnn) of the program
+struct Decoded {
+    address: u16,
+    words: Vec<u16>,
+}
+
+/// Splits the program into instructions, the last odd byte is left out
+fn decode(bytes: &[u8], origin: u16) -> Vec<Decoded> {
+    let words: Vec<u16> = bytes.chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| ((pair[0] as u16) << 8) | pair[1] as u16)
+        .collect();
+
+    let mut decoded = Vec::new();
+    let mut idx = 0;
+    while idx < words.len() {
+        let address = origin.wrapping_add((idx * 2) as u16);
+        let long = Instruction::decode(words[idx]) == Ok(Instruction::LdILong);
+        let len = if long && idx + 1 < words.len() { 2 } else { 1 };
+        decoded.push(Decoded { address, words: words[idx..idx + len].to_vec() });
+        idx += len;
+    }
+    decoded
+}
+
+/// Disassembles a program loaded at `origin` into one line per instruction.
+///
+/// Each line holds the mnemonic followed by a comment with the address and
+/// the raw opcode. Words that are not instructions become `dw` data lines,
+/// a trailing odd byte a `db` line. Targets of `JP addr` and `CALL addr`
+/// inside the program get labels and an origin other than 0x200 an `org`
+/// line, so the output can be fed back to the assembler.
+pub fn disassemble(bytes: &[u8], origin: u16) -> String {
+    let decoded = decode(bytes, origin);
+    let starts: BTreeSet<u16> = decoded.iter().map(|d| d.address).collect();
+    let targets: BTreeSet<u16> = decoded.iter()
+        .filter_map(|d| match Instruction::decode(d.words[0]) {
+            Ok(Instruction::Jp(nnn)) | Ok(Instruction::Call(nnn)) => Some(nnn),
+            _ => None,
+        })
+        .filter(|addr| starts.contains(addr))
+        .collect();
+    let target = |addr: u16| {
+        if targets.contains(&addr) { label(addr) } else { format!("0x{:03X}", addr) }
+    };
+
+    let mut out = String::new();
+    if origin != INTERPRETER_END {
+        out.push_str(&format!("    org 0x{:03X}\n", origin));
+    }
+    for d in &decoded {
+        if targets.contains(&d.address) {
+            out.push_str(&format!("{}:\n", label(d.address)));
+        }
+        let text = mnemonic_with_labels(d.words[0], d.words.get(1).cloned(), &target)
+            .unwrap_or_else(|| format!("dw 0x{:04X}", d.words[0]));
+        let raw: Vec<String> = d.words.iter().map(|w| format!("{:04X}", w)).collect();
+        out.push_str(&format!("    {:<width$}; 0x{:03X}: {}\n", text, d.address, raw.join(" "),
+                              width = COMMENT_COLUMN - 4));
+    }
+    if bytes.len() % 2 == 1 {
+        let address = origin.wrapping_add(bytes.len() as u16 - 1);
+        let text = format!("db 0x{:02X}", bytes[bytes.len() - 1]);
+        out.push_str(&format!("    {:<width$}; 0x{:03X}\n", text, address, width = COMMENT_COLUMN - 4));
+    }
+    out
+}
@@ -0,0 +1,107 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors reported by the interpreter and its frontends
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The program does not fit in the memory available for programs
+    RomTooLarge { size: usize, max: usize },
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// A frontend could not initialize its window, audio device or input
+    BackendInit(String),
+    /// A frontend's window, audio device or input failed while running
+    Backend(String),
+    /// The instruction at `address` is not part of the instruction set
+    InvalidOpcode { address: u16, opcode: u16 },
+    /// A CALL at `pc` found the stack full, `stack` holds the return
+    /// addresses on the stack at that point, oldest first
+    StackOverflow { pc: u16, stack: Vec<u16> },
+    /// A RET at `pc` found the stack empty
+    StackUnderflow { pc: u16, stack: Vec<u16> },
+    /// The instruction at `pc` accessed memory past the end of the address space
+    MemoryOutOfBounds { pc: u16, address: usize },
+    /// The assembler rejected the statement at `line` of `file`, or of the
+    /// source text if there is no file
+    Assemble { file: Option<PathBuf>, line: usize, message: String },
+    /// A save state could not be loaded
+    InvalidState(String),
+    /// A binary trace could not be read
+    InvalidTrace(String),
+    /// An input movie could not be read or played
+    InvalidMovie(String),
+    /// A keymap names a key that doesn't exist or its config file is malformed
+    InvalidKeymap(String),
+    /// The machine played back from a movie differs from the recording
+    /// after `frame` (counted from 0)
+    MovieDesync { frame: usize },
+    /// A run that was to stop at `address` ended without reaching it
+    NotReached { address: u16 },
+    /// Saving the machine to or restoring it from save state slot `slot`
+    /// failed
+    StateSlot { slot: u8, error: Box<Chip8Error> },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "program is {} bytes, at most {} bytes fit in memory", size, max)
+            },
+            Chip8Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Chip8Error::BackendInit(ref msg) => write!(f, "could not initialize frontend: {}", msg),
+            Chip8Error::Backend(ref msg) => write!(f, "frontend error: {}", msg),
+            Chip8Error::InvalidOpcode { address, opcode } => {
+                write!(f, "invalid opcode 0x{:04x} at 0x{:03x}", opcode, address)
+            },
+            Chip8Error::StackOverflow { pc, ref stack } => {
+                write!(f, "stack overflow at 0x{:03x}, call stack: {}", pc, format_stack(stack))
+            },
+            Chip8Error::StackUnderflow { pc, ref stack } => {
+                write!(f, "stack underflow at 0x{:03x}, call stack: {}", pc, format_stack(stack))
+            },
+            Chip8Error::MemoryOutOfBounds { pc, address } => {
+                write!(f, "out of bounds memory access to 0x{:x} at 0x{:03x}", address, pc)
+            },
+            Chip8Error::Assemble { ref file, line, ref message } => match *file {
+                Some(ref file) => write!(f, "{}:{}: {}", file.display(), line, message),
+                None => write!(f, "line {}: {}", line, message),
+            },
+            Chip8Error::InvalidState(ref reason) => write!(f, "invalid save state: {}", reason),
+            Chip8Error::InvalidTrace(ref reason) => write!(f, "invalid trace: {}", reason),
+            Chip8Error::InvalidMovie(ref reason) => write!(f, "invalid movie: {}", reason),
+            Chip8Error::InvalidKeymap(ref reason) => write!(f, "invalid keymap: {}", reason),
+            Chip8Error::MovieDesync { frame } => {
+                write!(f, "the movie desynced at frame {}, the run differs from the recording", frame)
+            },
+            Chip8Error::NotReached { address } => {
+                write!(f, "the program never reached 0x{:03x}", address)
+            },
+            Chip8Error::StateSlot { slot, ref error } => write!(f, "save state slot {}: {}", slot, error),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            Chip8Error::Io(ref e) => Some(e),
+            Chip8Error::StateSlot { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
+
+/// Formats return addresses as `[0x202, 0x3a4]`
+fn format_stack(stack: &[u16]) -> String {
+    let entries: Vec<String> = stack.iter().map(|addr| format!("0x{:03x}", addr)).collect();
+    format!("[{}]", entries.join(", "))
+}
@@ -0,0 +1,305 @@
+/// Width of the chip-8 screen in pixels
+pub const SCREEN_WIDTH: u8 = 64;
+/// Height of the chip-8 screen in pixels
+pub const SCREEN_HEIGHT: u8 = 32;
+/// Width of the SUPER-CHIP high resolution screen in pixels
+pub const HIRES_SCREEN_WIDTH: u8 = 128;
+/// Height of the SUPER-CHIP high resolution screen in pixels
+pub const HIRES_SCREEN_HEIGHT: u8 = 64;
+const MAX_SPRITE_LENGTH: u8 = 15;
+/// Number of bytes in a 16x16 SUPER-CHIP sprite
+pub const LARGE_SPRITE_LENGTH: u8 = 32;
+/// Number of XO-CHIP bitplanes
+pub const PLANE_COUNT: u8 = 2;
+/// RGB colors frontends show the pixel values in: off, first plane, second
+/// plane, both planes
+pub const PALETTE: [(u8, u8, u8); 4] = [
+    (0, 0, 0),
+    (255, 255, 255),
+    (170, 170, 170),
+    (85, 85, 85),
+];
+
+/// What drawing a sprite ran into
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Collision {
+    /// Rows of the sprite that erased a pixel that was on, on any plane
+    pub rows: u8,
+    /// Rows of the sprite cut off at the bottom of the screen
+    pub clipped: u8,
+}
+
+impl Collision {
+    /// Returns true if any pixel that was on got erased
+    pub fn erased(&self) -> bool {
+        self.rows > 0
+    }
+}
+
+/// The video memory of the interpreter.
+///
+/// Pixels are stored row by row. Each pixel holds one bit per XO-CHIP
+/// bitplane, bit 0 being the first plane, so a pixel has one of four colors;
+/// programs that never select the second plane only ever produce 0 and 1.
+/// Drawing, clearing and scrolling only affect the selected planes.
+///
+/// The framebuffer keeps track of whether it changed since the last time a
+/// frontend presented it so that frontends only redraw when necessary.
+pub struct Framebuffer {
+    width: u8,
+    height: u8,
+    memory: Vec<u8>,
+    planes: u8,
+    dirty: bool,
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Framebuffer {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            memory: vec![0; (SCREEN_WIDTH as usize) * (SCREEN_HEIGHT as usize)],
+            planes: 0x1,
+            dirty: true,
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Width of the framebuffer in pixels
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// Height of the framebuffer in pixels
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// The pixels of the framebuffer, row by row, as bitplane values (0 - 3)
+    pub fn pixels(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Returns true if the pixel at (x, y) is lit on any plane, off-screen
+    /// pixels are off
+    pub fn pixel(&self, x: u8, y: u8) -> bool {
+        self.color(x, y) != 0
+    }
+
+    /// Returns the bitplane value (0 - 3) of the pixel at (x, y)
+    pub fn color(&self, x: u8, y: u8) -> u8 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        self.memory[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Returns true in the 128x64 high resolution mode
+    pub fn is_hires(&self) -> bool {
+        self.width == HIRES_SCREEN_WIDTH
+    }
+
+    /// Switches between the 64x32 and the 128x64 resolution, clearing the screen
+    pub fn set_hires(&mut self, hires: bool) {
+        if hires {
+            self.width = HIRES_SCREEN_WIDTH;
+            self.height = HIRES_SCREEN_HEIGHT;
+        } else {
+            self.width = SCREEN_WIDTH;
+            self.height = SCREEN_HEIGHT;
+        }
+        self.memory = vec![0; (self.width as usize) * (self.height as usize)];
+        self.dirty = true;
+    }
+
+    /// Switches between the 64x32 and the 128x64 resolution keeping the
+    /// picture, as SUPER-CHIP 1.1 does: a low resolution pixel becomes a 2x2
+    /// square and a 2x2 square the low resolution pixel of its top left
+    pub fn resize(&mut self, hires: bool) {
+        if hires == self.is_hires() {
+            return;
+        }
+        let old_width = self.width as usize;
+        let old = ::std::mem::take(&mut self.memory);
+        self.set_hires(hires);
+        let width = self.width as usize;
+        for (idx, pixel) in self.memory.iter_mut().enumerate() {
+            let (x, y) = (idx % width, idx / width);
+            *pixel = if hires { old[(y / 2) * old_width + x / 2] } else { old[(y * 2) * old_width + x * 2] };
+        }
+    }
+
+    /// The bitmask of planes that drawing, clearing and scrolling affect
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Selects the planes that drawing, clearing and scrolling affect
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << PLANE_COUNT) - 1);
+    }
+
+    /// Number of selected planes, a sprite holds data for each of them
+    pub fn selected_plane_count(&self) -> u8 {
+        self.planes.count_ones() as u8
+    }
+
+    /// Replaces the contents with saved ones, returns false (leaving the
+    /// framebuffer untouched) if they don't make up a valid screen
+    pub(crate) fn restore(&mut self, width: u8, height: u8, planes: u8, pixels: &[u8]) -> bool {
+        let lores = width == SCREEN_WIDTH && height == SCREEN_HEIGHT;
+        let hires = width == HIRES_SCREEN_WIDTH && height == HIRES_SCREEN_HEIGHT;
+        if !(lores || hires) || pixels.len() != width as usize * height as usize {
+            return false;
+        }
+        self.width = width;
+        self.height = height;
+        self.memory = pixels.to_vec();
+        self.select_planes(planes);
+        self.dirty = true;
+        true
+    }
+
+    /// Returns true if the framebuffer changed since it was last marked clean
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Marks the framebuffer as presented
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+
+    /// XORs the sprite onto the selected planes at (x, y). The sprite holds
+    /// the rows for each selected plane in turn, lowest plane first.
+    /// The starting position wraps around the screen, the rest of the sprite
+    /// is either clipped at the edges or, if `wrap` is set, wrapped around to
+    /// the other side.
+    /// Returns the rows that erased a pixel that was on and the rows clipped.
+    pub fn draw(&mut self, x: u8, y: u8, sprite: &[u8], wrap: bool) -> Collision {
+        let count = self.selected_plane_count() as usize;
+        if count == 0 || sprite.len() / count > MAX_SPRITE_LENGTH as usize {
+            return Collision::default();
+        }
+        self.draw_planes(x, y, sprite, 1, wrap)
+    }
+
+    /// Draws 16x16 sprites (two bytes per row) like `draw`
+    pub fn draw_large(&mut self, x: u8, y: u8, sprite: &[u8], wrap: bool) -> Collision {
+        let count = self.selected_plane_count() as usize;
+        if count == 0 || sprite.len() != count * LARGE_SPRITE_LENGTH as usize {
+            return Collision::default();
+        }
+        self.draw_planes(x, y, sprite, 2, wrap)
+    }
+
+    fn draw_planes(&mut self, x: u8, y: u8, sprite: &[u8], row_bytes: usize, wrap: bool) -> Collision {
+        let len = sprite.len() / self.selected_plane_count() as usize;
+        // bit n for row n of the sprite
+        let mut erased_rows = 0u32;
+        let mut clipped = 0;
+        let mut data = sprite.chunks(::std::cmp::max(len, 1));
+        for plane in 0..PLANE_COUNT {
+            let bit = 1 << plane;
+            if self.planes & bit != 0 {
+                let rows = data.next().unwrap_or(&[]);
+                let (erased, plane_clipped) = self.blit(x, y, rows, row_bytes, wrap, bit);
+                erased_rows |= erased;
+                clipped = ::std::cmp::max(clipped, plane_clipped);
+            }
+        }
+        self.dirty = true;
+        Collision { rows: erased_rows.count_ones() as u8, clipped }
+    }
+
+    /// XORs a sprite `row_bytes` bytes wide onto a single plane. Returns the
+    /// rows that erased a pixel, bit n for row n, and the number of rows
+    /// clipped at the bottom.
+    fn blit(&mut self, x: u8, y: u8, sprite: &[u8], row_bytes: usize, wrap: bool, plane: u8) -> (u32, u8) {
+        let mut erased = 0;
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let x = x as usize % width;
+        let y = y as usize % height;
+
+        let rows = sprite.chunks(row_bytes).count();
+        for (row, bytes) in sprite.chunks(row_bytes).enumerate() {
+            let mut py = y + row;
+            if py >= height {
+                if !wrap {
+                    return (erased, (rows - row) as u8);
+                }
+                py %= height;
+            }
+            for bit in 0..(bytes.len() * 8) {
+                let mut px = x + bit;
+                if px >= width {
+                    if !wrap {
+                        break;
+                    }
+                    px %= width;
+                }
+                if (bytes[bit / 8] >> (7 - bit % 8)) & 0x1 == 0 {
+                    continue;
+                }
+                let idx = py * width + px;
+                if self.memory[idx] & plane != 0 {
+                    erased |= 1 << row;
+                }
+                self.memory[idx] ^= plane;
+            }
+        }
+        (erased, 0)
+    }
+
+    /// Turns off every pixel of the selected planes
+    pub fn clear(&mut self) {
+        let keep = !self.planes;
+        for pixel in self.memory.iter_mut() {
+            *pixel &= keep;
+        }
+        self.dirty = true;
+    }
+
+    /// Moves the selected planes down `rows` pixels, the top rows become blank
+    pub fn scroll_down(&mut self, rows: u8) {
+        let shift = ::std::cmp::min(rows as usize, self.height as usize) * self.width as usize;
+        self.shift_planes(|src| src.checked_sub(shift));
+    }
+
+    /// Moves the selected planes up `rows` pixels, the bottom rows become blank
+    pub fn scroll_up(&mut self, rows: u8) {
+        let shift = ::std::cmp::min(rows as usize, self.height as usize) * self.width as usize;
+        let len = self.memory.len();
+        self.shift_planes(|dst| if dst + shift < len { Some(dst + shift) } else { None });
+    }
+
+    /// Moves the selected planes right `columns` pixels, the leftmost columns
+    /// become blank
+    pub fn scroll_right(&mut self, columns: u8) {
+        let width = self.width as usize;
+        let shift = ::std::cmp::min(columns as usize, width);
+        self.shift_planes(|dst| if dst % width >= shift { Some(dst - shift) } else { None });
+    }
+
+    /// Moves the selected planes left `columns` pixels, the rightmost columns
+    /// become blank
+    pub fn scroll_left(&mut self, columns: u8) {
+        let width = self.width as usize;
+        let shift = ::std::cmp::min(columns as usize, width);
+        self.shift_planes(|dst| if dst % width + shift < width { Some(dst + shift) } else { None });
+    }
+
+    /// Replaces the selected planes of every pixel with those of the pixel
+    /// `source` maps it to, or blanks them if it maps to none
+    fn shift_planes<F: Fn(usize) -> Option<usize>>(&mut self, source: F) {
+        let planes = self.planes;
+        let old = self.memory.clone();
+        for (idx, pixel) in self.memory.iter_mut().enumerate() {
+            let moved = source(idx).map(|src| old[src] & planes).unwrap_or(0);
+            *pixel = (old[idx] & !planes) | moved;
+        }
+        self.dirty = true;
+    }
+}
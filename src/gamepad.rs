@@ -0,0 +1,158 @@
+//! Game controllers on the hex keypad.
+//!
+//! Controller buttons are host keys of a `Keymap` like keyboard keys, named
+//! `Pad A`, `Pad Start`, `Pad Up` and so on (see `PadButton`), so they are
+//! mapped per program in the keymap file. The left stick pushed past its dead
+//! zone presses the same directions as the D-pad. `Gamepads` follows the
+//! controllers from their events, which a frontend translates from its own,
+//! and works out the hex keys they hold.
+
+use std::collections::{BTreeMap, BTreeSet};
+use interpreter::KEY_COUNT;
+use keymap::Keymap;
+
+/// How far the stick has to be pushed, out of 32767, to press a direction
+pub const STICK_DEAD_ZONE: i16 = 8000;
+
+/// A button of a controller with the layout of an Xbox 360 pad, or a
+/// direction of its D-pad
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PadButton {
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+static PAD_BUTTONS: [(PadButton, &str); 15] = [
+    (PadButton::A, "Pad A"),
+    (PadButton::B, "Pad B"),
+    (PadButton::X, "Pad X"),
+    (PadButton::Y, "Pad Y"),
+    (PadButton::Back, "Pad Back"),
+    (PadButton::Guide, "Pad Guide"),
+    (PadButton::Start, "Pad Start"),
+    (PadButton::LeftStick, "Pad LeftStick"),
+    (PadButton::RightStick, "Pad RightStick"),
+    (PadButton::LeftShoulder, "Pad LeftShoulder"),
+    (PadButton::RightShoulder, "Pad RightShoulder"),
+    (PadButton::Up, "Pad Up"),
+    (PadButton::Down, "Pad Down"),
+    (PadButton::Left, "Pad Left"),
+    (PadButton::Right, "Pad Right"),
+];
+
+impl PadButton {
+    /// The name of the button in a keymap
+    pub fn name(self) -> &'static str {
+        PAD_BUTTONS.iter().find(|entry| entry.0 == self).unwrap().1
+    }
+
+    /// The button with the given keymap name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        PAD_BUTTONS.iter().find(|entry| entry.1.eq_ignore_ascii_case(name)).map(|entry| entry.0)
+    }
+}
+
+/// What happened to a controller, identified by a number that stays the
+/// same while it is connected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadEvent {
+    /// A controller was connected
+    Added(i32),
+    /// A controller was disconnected, whatever it held is released
+    Removed(i32),
+    Button { pad: i32, button: PadButton, pressed: bool },
+    /// The left stick moved along the horizontal or vertical axis, negative
+    /// values being left or up
+    Stick { pad: i32, horizontal: bool, value: i16 },
+}
+
+/// What one controller holds
+#[derive(Default)]
+struct Pad {
+    buttons: BTreeSet<PadButton>,
+    /// Directions the stick presses
+    stick: BTreeSet<PadButton>,
+}
+
+/// The controllers connected and what they hold
+#[derive(Default)]
+pub struct Gamepads {
+    pads: BTreeMap<i32, Pad>,
+}
+
+impl Gamepads {
+    /// Starts with no controller connected
+    pub fn new() -> Self {
+        Gamepads::default()
+    }
+
+    /// Follows an event of a controller. Events of controllers that were
+    /// never added count as if they had been.
+    pub fn handle(&mut self, event: PadEvent) {
+        match event {
+            PadEvent::Added(pad) => {
+                self.pads.entry(pad).or_default();
+            },
+            PadEvent::Removed(pad) => {
+                self.pads.remove(&pad);
+            },
+            PadEvent::Button { pad, button, pressed } => {
+                let buttons = &mut self.pads.entry(pad).or_default().buttons;
+                if pressed {
+                    buttons.insert(button);
+                } else {
+                    buttons.remove(&button);
+                }
+            },
+            PadEvent::Stick { pad, horizontal, value } => {
+                let (negative, positive) = if horizontal {
+                    (PadButton::Left, PadButton::Right)
+                } else {
+                    (PadButton::Up, PadButton::Down)
+                };
+                let stick = &mut self.pads.entry(pad).or_default().stick;
+                stick.remove(&negative);
+                stick.remove(&positive);
+                if value < -STICK_DEAD_ZONE {
+                    stick.insert(negative);
+                } else if value > STICK_DEAD_ZONE {
+                    stick.insert(positive);
+                }
+            },
+        }
+    }
+
+    /// The number of controllers connected
+    pub fn connected(&self) -> usize {
+        self.pads.len()
+    }
+
+    /// Returns true if any controller holds the button
+    pub fn is_held(&self, button: PadButton) -> bool {
+        self.pads.values().any(|pad| pad.buttons.contains(&button) || pad.stick.contains(&button))
+    }
+
+    /// The hex keys held through `keymap`, bit n for key n
+    pub fn hex_keys(&self, keymap: &Keymap) -> u16 {
+        (0..KEY_COUNT)
+            .filter(|key| {
+                keymap.host_keys(*key).iter()
+                    .filter_map(|name| PadButton::from_name(name))
+                    .any(|button| self.is_held(button))
+            })
+            .fold(0, |keys, key| keys | 1 << key)
+    }
+}
@@ -0,0 +1,499 @@
+//! A GDB remote serial protocol stub, so gdb, lldb or an IDE can debug a
+//! program over TCP.
+//!
+//! The registers, in the order `g` sends them and numbered from 0 for `p`
+//! and `P`, are V0 - VF (one byte each), I and PC (two bytes, big-endian like
+//! the machine) and SP, DT and ST (one byte each). Besides the registers and
+//! memory the stub supports software breakpoints (`Z0`), write, read and
+//! access watchpoints (`Z2` - `Z4`), single step, continue and interrupting
+//! a continue.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use backend::{Display, Audio, Input};
+use error::Chip8Error;
+use interpreter::{Interpreter, Pause};
+use runner::Runner;
+use watch::{Access, Watchpoint};
+
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+/// Largest packet the stub accepts, announced in `qSupported`
+const PACKET_SIZE: usize = 0x4000;
+/// Byte the debugger sends to interrupt a running program
+const INTERRUPT: u8 = 0x03;
+
+/// Signals in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGKILL: u8 = 9;
+const SIGSEGV: u8 = 11;
+
+/// Waits for a debugger on a TCP port
+pub struct GdbServer {
+    listener: TcpListener,
+}
+
+impl GdbServer {
+    /// Listens on `addr`, e.g. "127.0.0.1:9000". Port 0 picks a free port,
+    /// see `local_addr`.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, Chip8Error> {
+        Ok(GdbServer { listener: TcpListener::bind(addr)? })
+    }
+
+    /// The address the server listens on
+    pub fn local_addr(&self) -> Result<SocketAddr, Chip8Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts one debugger and serves it until it detaches, kills the
+    /// program or disconnects, or the window is closed. The program runs
+    /// through the runner while the debugger continues it.
+    pub fn serve<D, A, I>(&self, runner: &mut Runner<D, A, I>, intp: &mut Interpreter) -> Result<(), Chip8Error>
+        where D: Display, A: Audio, I: Input
+    {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+        Session { stream, pending: None, ack: true, over: false }.serve(runner, intp)
+    }
+}
+
+/// Why stepping or continuing ended
+enum Stop {
+    /// The program stopped by itself: a breakpoint, watchpoint or condition,
+    /// the end of a single step or the program exiting
+    Paused,
+    /// The debugger interrupted it
+    Interrupted,
+    /// The window was closed
+    Killed,
+}
+
+/// A connected debugger
+struct Session {
+    stream: TcpStream,
+    /// A byte `interrupted` read that was not an interrupt, the start of
+    /// the next packet
+    pending: Option<u8>,
+    /// Acknowledge packets, until the debugger turns it off
+    ack: bool,
+    /// Set once the session has to end
+    over: bool,
+}
+
+impl Session {
+    fn serve<D, A, I>(&mut self, runner: &mut Runner<D, A, I>, intp: &mut Interpreter) -> Result<(), Chip8Error>
+        where D: Display, A: Audio, I: Input
+    {
+        while !self.over {
+            let packet = match self.read_packet()? {
+                Some(packet) => packet,
+                None => break,
+            };
+            if let Some(reply) = self.handle(runner, intp, &packet)? {
+                self.write_packet(&reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes a packet and returns the reply, None for packets that get
+    /// none
+    fn handle<D, A, I>(&mut self, runner: &mut Runner<D, A, I>, intp: &mut Interpreter,
+                       packet: &str) -> Result<Option<String>, Chip8Error>
+        where D: Display, A: Audio, I: Input
+    {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => stop_reply(intp),
+            "g" => registers(intp).iter().map(|bytes| to_hex(bytes)).collect(),
+            "G" => status(write_registers(intp, args)),
+            "p" => {
+                let registers = registers(intp);
+                parse_hex(args)
+                    .and_then(|number| registers.get(number))
+                    .map_or_else(|| error_reply(1), |bytes| to_hex(bytes))
+            },
+            "P" => status(write_register(intp, args)),
+            "m" => read_memory(intp, args).unwrap_or_else(|| error_reply(1)),
+            "M" => status(write_memory(intp, args)),
+            "Z" | "z" => toggle_point(intp, command == "Z", args),
+            "s" | "c" => {
+                if set_resume_address(intp, args).is_none() {
+                    return Ok(Some(error_reply(1)));
+                }
+                let result = if command == "s" {
+                    intp.step().map(|_| Stop::Paused)
+                } else {
+                    self.resume(runner, intp)
+                };
+                runner.refresh(intp);
+                self.after_run(intp, result)
+            },
+            "q" => query(args),
+            "Q" if args == "StartNoAckMode" => {
+                self.write_packet("OK")?;
+                self.ack = false;
+                return Ok(None);
+            },
+            "H" => "OK".to_string(),
+            "D" => {
+                self.over = true;
+                "OK".to_string()
+            },
+            "k" => {
+                self.over = true;
+                return Ok(None);
+            },
+            _ => String::new(),
+        };
+        Ok(Some(reply))
+    }
+
+    /// The stop reply after stepping or continuing
+    fn after_run(&mut self, intp: &Interpreter, result: Result<Stop, Chip8Error>) -> String {
+        match result {
+            Ok(Stop::Paused) => stop_reply(intp),
+            Ok(Stop::Interrupted) => format!("S{:02x}", SIGINT),
+            Ok(Stop::Killed) => {
+                self.over = true;
+                format!("X{:02x}", SIGKILL)
+            },
+            Err(Chip8Error::InvalidOpcode { .. }) => format!("S{:02x}", SIGILL),
+            Err(_) => format!("S{:02x}", SIGSEGV),
+        }
+    }
+
+    /// Runs the program in real time until it stops by itself, the debugger
+    /// interrupts it or the window is closed
+    fn resume<D, A, I>(&mut self, runner: &mut Runner<D, A, I>, intp: &mut Interpreter) -> Result<Stop, Chip8Error>
+        where D: Display, A: Audio, I: Input
+    {
+        if intp.is_halted() {
+            return Ok(Stop::Paused);
+        }
+        let mut interrupted = false;
+        runner.run_while(intp, usize::MAX, || {
+            interrupted = self.interrupted()?;
+            Ok(!interrupted)
+        })?;
+        if interrupted {
+            Ok(Stop::Interrupted)
+        } else if intp.pause().is_some() || intp.is_halted() {
+            Ok(Stop::Paused)
+        } else {
+            Ok(Stop::Killed)
+        }
+    }
+
+    /// Returns true if the debugger sent an interrupt, without waiting for it
+    fn interrupted(&mut self) -> Result<bool, Chip8Error> {
+        if self.pending.is_some() {
+            return Ok(false);
+        }
+        self.stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let result = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the debugger disconnected").into()),
+            Ok(_) if byte[0] == INTERRUPT => Ok(true),
+            Ok(_) => {
+                self.pending = Some(byte[0]);
+                Ok(false)
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Reads the next packet, None if the debugger disconnected
+    fn read_packet(&mut self) -> Result<Option<String>, Chip8Error> {
+        loop {
+            // skip acknowledgements and interrupts of a program that is not
+            // running
+            match self.read_byte()? {
+                Some(b'$') => {},
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+            let mut data = Vec::new();
+            let mut checksum: u8 = 0;
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => {
+                        checksum = checksum.wrapping_add(byte);
+                        data.push(byte);
+                    },
+                    None => return Ok(None),
+                }
+            }
+            let mut sent = [0; 2];
+            for byte in sent.iter_mut() {
+                match self.read_byte()? {
+                    Some(value) => *byte = value,
+                    None => return Ok(None),
+                }
+            }
+            let valid = ::std::str::from_utf8(&sent).ok()
+                .and_then(|sent| u8::from_str_radix(sent, 16).ok()) == Some(checksum);
+            if !self.ack {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            if valid {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, Chip8Error> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(Some(byte));
+        }
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> Result<(), Chip8Error> {
+        let mut escaped = Vec::with_capacity(data.len());
+        for byte in data.bytes() {
+            if let b'#' | b'$' | b'}' | b'*' = byte {
+                escaped.push(b'}');
+                escaped.push(byte ^ 0x20);
+            } else {
+                escaped.push(byte);
+            }
+        }
+        let checksum = escaped.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(&escaped);
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+        self.stream.write_all(&packet)?;
+        Ok(())
+    }
+}
+
+/// Why the program is stopped, as a stop reply
+fn stop_reply(intp: &Interpreter) -> String {
+    if intp.is_halted() {
+        return "W00".to_string();
+    }
+    match intp.pause() {
+        // gdb has no fetch watchpoints, it is told about a plain trap
+        Some(Pause::Watchpoint { address, access, .. }) if access != Access::Fetch => {
+            let watchpoint = intp.watchpoints().iter()
+                .find(|watchpoint| watchpoint.matches(access, address as usize));
+            let kind = match watchpoint {
+                Some(watchpoint) if watchpoint.read && watchpoint.write => "awatch",
+                _ if access == Access::Write => "watch",
+                _ => "rwatch",
+            };
+            format!("T{:02x}{}:{:x};", SIGTRAP, kind, address)
+        },
+        _ => format!("S{:02x}", SIGTRAP),
+    }
+}
+
+/// The registers in `g` order, each as its bytes
+fn registers(intp: &Interpreter) -> Vec<Vec<u8>> {
+    let mut registers: Vec<Vec<u8>> = (0..16).map(|x| vec![intp.register(x).unwrap()]).collect();
+    registers.push(vec![(intp.index() >> 8) as u8, intp.index() as u8]);
+    registers.push(vec![(intp.pc() >> 8) as u8, intp.pc() as u8]);
+    registers.push(vec![intp.call_stack().len() as u8]);
+    registers.push(vec![intp.delay_timer()]);
+    registers.push(vec![intp.sound_timer()]);
+    registers
+}
+
+/// Sets register `number` from its bytes
+fn set_register(intp: &mut Interpreter, number: usize, bytes: &[u8]) -> Option<()> {
+    let word = || if bytes.len() == 2 { Some(((bytes[0] as u16) << 8) | bytes[1] as u16) } else { None };
+    let byte = || if bytes.len() == 1 { Some(bytes[0]) } else { None };
+    match number {
+        0..=15 => intp.set_register(number as u8, byte()?),
+        REG_I => intp.set_index(word()?),
+        REG_PC => intp.set_pc(word()?),
+        REG_SP => {
+            if !intp.set_stack_pointer(byte()?) {
+                return None;
+            }
+        },
+        REG_DT => intp.set_delay_timer(byte()?),
+        REG_ST => intp.set_sound_timer(byte()?),
+        _ => return None,
+    }
+    Some(())
+}
+
+/// `G XX...`: all the registers in `g` order
+fn write_registers(intp: &mut Interpreter, args: &str) -> Option<()> {
+    let mut bytes = &from_hex(args)?[..];
+    let sizes: Vec<usize> = registers(intp).iter().map(Vec::len).collect();
+    if bytes.len() != sizes.iter().sum::<usize>() {
+        return None;
+    }
+    for (number, size) in sizes.into_iter().enumerate() {
+        let (value, rest) = bytes.split_at(size);
+        set_register(intp, number, value)?;
+        bytes = rest;
+    }
+    Some(())
+}
+
+/// `P n=XX...`
+fn write_register(intp: &mut Interpreter, args: &str) -> Option<()> {
+    let (number, value) = split2(args, '=')?;
+    set_register(intp, parse_hex(number)?, &from_hex(value)?)
+}
+
+/// `m addr,length`, reads stop at the end of the memory
+fn read_memory(intp: &Interpreter, args: &str) -> Option<String> {
+    let (address, len) = split2(args, ',')?;
+    let (address, len) = (parse_hex(address)?, parse_hex(len)?);
+    let memory = intp.memory();
+    if address >= memory.len() {
+        return None;
+    }
+    let end = ::std::cmp::min(address.saturating_add(len), memory.len());
+    Some(to_hex(&memory[address..end]))
+}
+
+/// `M addr,length:XX...`
+fn write_memory(intp: &mut Interpreter, args: &str) -> Option<()> {
+    let (location, data) = split2(args, ':')?;
+    let (address, len) = split2(location, ',')?;
+    let (address, len) = (parse_hex(address)?, parse_hex(len)?);
+    let bytes = from_hex(data)?;
+    let memory = intp.memory_mut();
+    if bytes.len() != len || address.checked_add(len).is_none_or(|end| end > memory.len()) {
+        return None;
+    }
+    memory[address..address + len].copy_from_slice(&bytes);
+    Some(())
+}
+
+/// `Z type,addr,kind` and `z type,addr,kind`: breakpoints (type 0 and 1)
+/// and write, read and access watchpoints (types 2, 3 and 4), where `kind`
+/// is the number of bytes watched
+fn toggle_point(intp: &mut Interpreter, insert: bool, args: &str) -> String {
+    let fields = args.split(',').map(parse_hex).collect::<Option<Vec<usize>>>();
+    let (kind, address, len) = match fields.as_deref() {
+        Some(&[kind, address, len]) if address <= 0xffff => (kind, address as u16, len),
+        _ => return error_reply(1),
+    };
+    // the last byte watched, a length of 0 watching one
+    let end = ::std::cmp::min((address as usize).saturating_add(len.saturating_sub(1)), 0xffff) as u16;
+    let watchpoint = match kind {
+        0 | 1 => {
+            if insert {
+                intp.add_breakpoint(address);
+            } else {
+                intp.remove_breakpoint(address);
+            }
+            return "OK".to_string();
+        },
+        2 => Watchpoint::writes(address, end),
+        3 => Watchpoint::reads(address, end),
+        4 => Watchpoint { write: true, ..Watchpoint::reads(address, end) },
+        _ => return String::new(),
+    };
+    if insert {
+        intp.add_watchpoint(watchpoint);
+    } else if let Some(index) = intp.watchpoints().iter().position(|w| *w == watchpoint) {
+        intp.remove_watchpoint(index);
+    }
+    "OK".to_string()
+}
+
+/// `s [addr]` and `c [addr]` resume at `addr` if given
+fn set_resume_address(intp: &mut Interpreter, args: &str) -> Option<()> {
+    if !args.is_empty() {
+        let address = parse_hex(args).filter(|address| *address <= 0xffff)?;
+        intp.set_pc(address as u16);
+    }
+    Some(())
+}
+
+/// `q...` general queries
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+", PACKET_SIZE)
+    } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        let description = target_description();
+        let (offset, len) = match split2(range, ',').and_then(|(offset, len)| Some((parse_hex(offset)?, parse_hex(len)?))) {
+            Some(range) => range,
+            None => return error_reply(1),
+        };
+        let start = ::std::cmp::min(offset, description.len());
+        let end = ::std::cmp::min(start.saturating_add(len), description.len());
+        let more = if end < description.len() { "m" } else { "l" };
+        format!("{}{}", more, &description[start..end])
+    } else if args == "Attached" {
+        "1".to_string()
+    } else if args == "C" {
+        "QC1".to_string()
+    } else if args == "fThreadInfo" {
+        "m1".to_string()
+    } else if args == "sThreadInfo" {
+        "l".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// The register layout, for debuggers that ask for it
+fn target_description() -> String {
+    let mut registers = String::new();
+    for x in 0..16 {
+        registers.push_str(&format!("<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>", x));
+    }
+    registers.push_str("<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>");
+    registers.push_str("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>");
+    for name in &["sp", "dt", "st"] {
+        registers.push_str(&format!("<reg name=\"{}\" bitsize=\"8\" type=\"uint8\"/>", name));
+    }
+    format!("<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+             <target version=\"1.0\"><feature name=\"org.pschip8.chip8\">{}</feature></target>",
+            registers)
+}
+
+fn status(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => error_reply(1),
+    }
+}
+
+fn error_reply(code: u8) -> String {
+    format!("E{:02x}", code)
+}
+
+fn split2(text: &str, separator: char) -> Option<(&str, &str)> {
+    let at = text.find(separator)?;
+    Some((&text[..at], &text[at + 1..]))
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|at| u8::from_str_radix(&text[at..at + 2], 16).ok()).collect()
+}
@@ -0,0 +1,284 @@
+use std::error::Error;
+use std::fmt;
+
+/// A decoded instruction, named after its mnemonic in the instruction quick
+/// reference. Register operands are register numbers (0x0 - 0xf).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn - SYS addr
+    Sys(u16),
+    /// 00e0 - CLS
+    Cls,
+    /// 00ee - RET
+    Ret,
+    /// 1nnn - JP addr
+    Jp(u16),
+    /// 2nnn - CALL addr
+    Call(u16),
+    /// 3xkk - SE Vx, byte
+    SeByte(u8, u8),
+    /// 4xkk - SNE Vx, byte
+    SneByte(u8, u8),
+    /// 5xy0 - SE Vx, Vy
+    SeReg(u8, u8),
+    /// 6xkk - LD Vx, byte
+    LdByte(u8, u8),
+    /// 7xkk - ADD Vx, byte
+    AddByte(u8, u8),
+    /// 8xy0 - LD Vx, Vy
+    LdReg(u8, u8),
+    /// 8xy1 - OR Vx, Vy
+    Or(u8, u8),
+    /// 8xy2 - AND Vx, Vy
+    And(u8, u8),
+    /// 8xy3 - XOR Vx, Vy
+    Xor(u8, u8),
+    /// 8xy4 - ADD Vx, Vy
+    AddReg(u8, u8),
+    /// 8xy5 - SUB Vx, Vy
+    Sub(u8, u8),
+    /// 8xy6 - SHR Vx {, Vy}
+    Shr(u8, u8),
+    /// 8xy7 - SUBN Vx, Vy
+    Subn(u8, u8),
+    /// 8xye - SHL Vx {, Vy}
+    Shl(u8, u8),
+    /// 9xy0 - SNE Vx, Vy
+    SneReg(u8, u8),
+    /// Annn - LD I, addr
+    LdI(u16),
+    /// Bnnn - JP V0, addr
+    JpV0(u16),
+    /// Cxkk - RND Vx, byte
+    Rnd(u8, u8),
+    /// Dxyn - DRW Vx, Vy, n
+    Drw(u8, u8, u8),
+    /// Ex9e - SKP Vx
+    Skp(u8),
+    /// Exa1 - SKNP Vx
+    Sknp(u8),
+    /// Fx07 - LD Vx, DT
+    LdVxDt(u8),
+    /// Fx0a - LD Vx, K
+    LdVxK(u8),
+    /// Fx15 - LD DT, Vx
+    LdDtVx(u8),
+    /// Fx18 - LD ST, Vx
+    LdStVx(u8),
+    /// Fx1e - ADD I, Vx
+    AddI(u8),
+    /// Fx29 - LD F, Vx
+    LdF(u8),
+    /// Fx33 - LD B, Vx
+    LdB(u8),
+    /// Fx55 - LD [I], Vx
+    LdMemVx(u8),
+    /// Fx65 - LD Vx, [I]
+    LdVxMem(u8),
+
+    /// 00cn - SCD n
+    Scd(u8),
+    /// 00fb - SCR
+    Scr,
+    /// 00fc - SCL
+    Scl,
+    /// 00fd - EXIT
+    Exit,
+    /// 00fe - LOW
+    Low,
+    /// 00ff - HIGH
+    High,
+    /// Fx30 - LD HF, Vx
+    LdHf(u8),
+    /// Fx75 - LD R, Vx
+    LdRVx(u8),
+    /// Fx85 - LD Vx, R
+    LdVxR(u8),
+
+    /// 00dn - SCU n
+    Scu(u8),
+    /// 5xy2 - LD [I], Vx - Vy
+    LdMemRange(u8, u8),
+    /// 5xy3 - LD Vx - Vy, [I]
+    LdRangeMem(u8, u8),
+    /// F000 nnnn - LD I, long nnnn. The address is not part of the opcode,
+    /// it is the word that follows it.
+    LdILong,
+    /// Fn01 - PLANE n
+    Plane(u8),
+    /// F002 - AUDIO
+    Audio,
+    /// Fx3a - PITCH Vx
+    Pitch(u8),
+}
+
+/// The opcode is not part of the instruction set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownOpcode(pub u16);
+
+impl fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode 0x{:04x}", self.0)
+    }
+}
+
+impl Error for UnknownOpcode {}
+
+impl Instruction {
+    /// Decodes an opcode
+    pub fn decode(opcode: u16) -> Result<Instruction, UnknownOpcode> {
+        use self::Instruction::*;
+
+        let x = ((opcode >> 8) & 0x000f) as u8;
+        let y = ((opcode >> 4) & 0x000f) as u8;
+        let n = (opcode & 0x000f) as u8;
+        let kk = (opcode & 0x00ff) as u8;
+        let nnn = opcode & 0x0fff;
+
+        let instruction = match opcode >> 12 {
+            0x0 => match nnn {
+                0x0e0 => Cls,
+                0x0ee => Ret,
+                0x0c0..=0x0cf => Scd(n),
+                0x0d0..=0x0df => Scu(n),
+                0x0fb => Scr,
+                0x0fc => Scl,
+                0x0fd => Exit,
+                0x0fe => Low,
+                0x0ff => High,
+                _ => Sys(nnn),
+            },
+            0x1 => Jp(nnn),
+            0x2 => Call(nnn),
+            0x3 => SeByte(x, kk),
+            0x4 => SneByte(x, kk),
+            0x5 => match n {
+                0x0 => SeReg(x, y),
+                0x2 => LdMemRange(x, y),
+                0x3 => LdRangeMem(x, y),
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            0x6 => LdByte(x, kk),
+            0x7 => AddByte(x, kk),
+            0x8 => match n {
+                0x0 => LdReg(x, y),
+                0x1 => Or(x, y),
+                0x2 => And(x, y),
+                0x3 => Xor(x, y),
+                0x4 => AddReg(x, y),
+                0x5 => Sub(x, y),
+                0x6 => Shr(x, y),
+                0x7 => Subn(x, y),
+                0xe => Shl(x, y),
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            0x9 if n == 0 => SneReg(x, y),
+            0xa => LdI(nnn),
+            0xb => JpV0(nnn),
+            0xc => Rnd(x, kk),
+            0xd => Drw(x, y, n),
+            0xe => match kk {
+                0x9e => Skp(x),
+                0xa1 => Sknp(x),
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            0xf => match kk {
+                0x00 if x == 0 => LdILong,
+                0x01 => Plane(x),
+                0x02 if x == 0 => Audio,
+                0x07 => LdVxDt(x),
+                0x0a => LdVxK(x),
+                0x15 => LdDtVx(x),
+                0x18 => LdStVx(x),
+                0x1e => AddI(x),
+                0x29 => LdF(x),
+                0x30 => LdHf(x),
+                0x33 => LdB(x),
+                0x3a => Pitch(x),
+                0x55 => LdMemVx(x),
+                0x65 => LdVxMem(x),
+                0x75 => LdRVx(x),
+                0x85 => LdVxR(x),
+                _ => return Err(UnknownOpcode(opcode)),
+            },
+            _ => return Err(UnknownOpcode(opcode)),
+        };
+        Ok(instruction)
+    }
+
+    /// Encodes the instruction, the inverse of `decode`. Operands wider than
+    /// their field are truncated.
+    pub fn encode(&self) -> u16 {
+        use self::Instruction::*;
+
+        fn xy(op: u16, x: u8, y: u8, n: u16) -> u16 {
+            op | ((x as u16 & 0xf) << 8) | ((y as u16 & 0xf) << 4) | n
+        }
+        fn xkk(op: u16, x: u8, kk: u8) -> u16 {
+            op | ((x as u16 & 0xf) << 8) | kk as u16
+        }
+
+        match *self {
+            Sys(nnn) => nnn & 0x0fff,
+            Cls => 0x00e0,
+            Ret => 0x00ee,
+            Jp(nnn) => 0x1000 | (nnn & 0x0fff),
+            Call(nnn) => 0x2000 | (nnn & 0x0fff),
+            SeByte(x, kk) => xkk(0x3000, x, kk),
+            SneByte(x, kk) => xkk(0x4000, x, kk),
+            SeReg(x, y) => xy(0x5000, x, y, 0x0),
+            LdByte(x, kk) => xkk(0x6000, x, kk),
+            AddByte(x, kk) => xkk(0x7000, x, kk),
+            LdReg(x, y) => xy(0x8000, x, y, 0x0),
+            Or(x, y) => xy(0x8000, x, y, 0x1),
+            And(x, y) => xy(0x8000, x, y, 0x2),
+            Xor(x, y) => xy(0x8000, x, y, 0x3),
+            AddReg(x, y) => xy(0x8000, x, y, 0x4),
+            Sub(x, y) => xy(0x8000, x, y, 0x5),
+            Shr(x, y) => xy(0x8000, x, y, 0x6),
+            Subn(x, y) => xy(0x8000, x, y, 0x7),
+            Shl(x, y) => xy(0x8000, x, y, 0xe),
+            SneReg(x, y) => xy(0x9000, x, y, 0x0),
+            LdI(nnn) => 0xa000 | (nnn & 0x0fff),
+            JpV0(nnn) => 0xb000 | (nnn & 0x0fff),
+            Rnd(x, kk) => xkk(0xc000, x, kk),
+            Drw(x, y, n) => xy(0xd000, x, y, n as u16 & 0xf),
+            Skp(x) => xkk(0xe000, x, 0x9e),
+            Sknp(x) => xkk(0xe000, x, 0xa1),
+            LdVxDt(x) => xkk(0xf000, x, 0x07),
+            LdVxK(x) => xkk(0xf000, x, 0x0a),
+            LdDtVx(x) => xkk(0xf000, x, 0x15),
+            LdStVx(x) => xkk(0xf000, x, 0x18),
+            AddI(x) => xkk(0xf000, x, 0x1e),
+            LdF(x) => xkk(0xf000, x, 0x29),
+            LdB(x) => xkk(0xf000, x, 0x33),
+            LdMemVx(x) => xkk(0xf000, x, 0x55),
+            LdVxMem(x) => xkk(0xf000, x, 0x65),
+            Scd(n) => 0x00c0 | (n as u16 & 0xf),
+            Scr => 0x00fb,
+            Scl => 0x00fc,
+            Exit => 0x00fd,
+            Low => 0x00fe,
+            High => 0x00ff,
+            LdHf(x) => xkk(0xf000, x, 0x30),
+            LdRVx(x) => xkk(0xf000, x, 0x75),
+            LdVxR(x) => xkk(0xf000, x, 0x85),
+            Scu(n) => 0x00d0 | (n as u16 & 0xf),
+            LdMemRange(x, y) => xy(0x5000, x, y, 0x2),
+            LdRangeMem(x, y) => xy(0x5000, x, y, 0x3),
+            LdILong => 0xf000,
+            Plane(n) => xkk(0xf000, n, 0x01),
+            Audio => 0xf002,
+            Pitch(x) => xkk(0xf000, x, 0x3a),
+        }
+    }
+
+    /// Number of bytes the instruction takes in memory, including the address
+    /// following `LD I, long`
+    pub fn width(&self) -> u16 {
+        match *self {
+            Instruction::LdILong => 4,
+            _ => 2,
+        }
+    }
+}
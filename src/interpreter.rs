@@ -1,23 +1,16 @@
-extern crate sdl2;
-
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
-use std::thread;
-use std::time::{Duration, SystemTime};
-use std::collections::HashSet;
-use std::process;
-use cpu::Cpu;
-
-use self::sdl2::render::Renderer;
-use self::sdl2::event::Event;
-use self::sdl2::keyboard;
-use self::sdl2::keyboard::Keycode;
-use self::sdl2::keyboard::Scancode;
-use self::sdl2::{VideoSubsystem, Sdl, EventPump};
-use self::sdl2::audio::{AudioDevice, AudioCallback, AudioSpecDesired};
-use self::sdl2::pixels::Color;
-use self::sdl2::rect::Point;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use cpu::{Cpu, Reg};
+use error::Chip8Error;
+use framebuffer::{Framebuffer, LARGE_SPRITE_LENGTH};
+use instruction::Instruction;
+use quirks::{Quirks, QUIRK_COUNT};
+use rng::{RandomSource, SeededRandom};
+use state::{self, StateReader, StateWriter};
+use trace::{TraceEntry, Tracer};
+use watch::{Access, Condition, Watchpoint};
 
 /// # Instructions Quick Reference
 /// * 0nnn - SYS addr:       =>   jmp to nnn
@@ -35,20 +28,20 @@ use self::sdl2::rect::Point;
 /// * 8xy2 - AND Vx, Vy:     =>   set Vx = Vx AND Vy
 /// * 8xy3 - XOR Vx, Vy:     =>   set Vx = Vx XOR Vy
 /// * 8xy4 - ADD Vx, Vy:     =>   set Vx = Vx + Vy, VF = 1 (iff result > 255) : 0
-/// * 8xy5 - SUB Vx, Vy:     =>   set Vx = Vx - Vy, VF = 1 (iff Vx > Vy) : 0
+/// * 8xy5 - SUB Vx, Vy:     =>   set Vx = Vx - Vy, VF = 1 (iff Vx >= Vy) : 0
 /// * 8xy6 - SHR Vx {, Vy}:  =>   set Vx = Vx SHR 1, VF = 1 (iff lsb(Vx) = 1) : 0
-/// * 8xy7 - SUBN Vx, Vy:    =>   set Vx = Vy - Vx, VF = 1 (iff Vy > Vx) : 0
+/// * 8xy7 - SUBN Vx, Vy:    =>   set Vx = Vy - Vx, VF = 1 (iff Vy >= Vx) : 0
 /// * 8xye - SHL Vx {, Vy}:  =>   set Vx = Vx SHL 1, VF = 1 (iff msb(Vx) = 1) : 0
 /// * 9xy0 - SNE Vx, Vy:     =>   skip next instruction if Vx != Vy
 /// * Annn - LD I, addr:     =>   set I = nnn
 /// * Bnnn - JP V0, addr:    =>   jmp to nnn + V0, pc = nnn + v0
 /// * Cxkk - RND Vx, byte:   =>   set Vx = random byte AND kk
-/// * Dxyn - DRW Vx, Vy, n:  =>   display n-byte sprite starting at addr I at (Vx, Vy)
-///                          =>   VF = 1, if after anything on screen is erased
+/// * Dxyn - DRW Vx, Vy, n:  =>   display n-byte sprite starting at addr I at (Vx, Vy),
+///   VF = 1 if anything on screen is erased
 /// * Ex9e - SKP Vx:         =>   skip next instruction if key with value of Vx is pressed
 /// * Exa1 - SKNP Vx:        =>   skip next instruction if key with value of Vx is not pressed
 /// * Fx07 - LD Vx, DT:      =>   set Vx = delay timer value
-/// * Fx0a - LD Vx, K:       =>   wait for input and store the value of the key press in Vx
+/// * Fx0a - LD Vx, K:       =>   wait for a key press (or release) and store the key in Vx
 /// * Fx15 - LD DT, Vx:      =>   set the value of the delay timer to be the value of Vx
 /// * Fx18 - LD ST, Vx:      =>   set the the value of the sound timer to be the value of Vx
 /// * Fx1e - ADD I, Vx:      =>   set I = I + Vx
@@ -56,19 +49,43 @@ use self::sdl2::rect::Point;
 /// * Fx33 - LD B, Vx:       =>   store BCD representation of Vx in mem addresses I, I+1 and I+2
 /// * Fx55 - LD [I], Vx:     =>   store values of registers V0 through Vx at address starting at I
 /// * Fx65 - LD Vx, [I]:     =>   starting from memory address I, populate registers V0 to Vx
-
-
-const INTERPRETER_END: u16 = 512;
+///
+/// # SUPER-CHIP 1.1 Instructions
+/// * 00cn - SCD n:          =>   scroll the screen down n pixels
+/// * 00fb - SCR:            =>   scroll the screen right 4 pixels
+/// * 00fc - SCL:            =>   scroll the screen left 4 pixels
+/// * 00fd - EXIT:           =>   stop the interpreter
+/// * 00fe - LOW:            =>   switch to the 64x32 low resolution screen
+/// * 00ff - HIGH:           =>   switch to the 128x64 high resolution screen
+/// * Dxy0 - DRW Vx, Vy, 0:  =>   display 16x16 sprite starting at addr I at (Vx, Vy)
+/// * Fx30 - LD HF, Vx:      =>   set I = location of big (8x10) font sprite for digit Vx
+/// * Fx75 - LD R, Vx:       =>   store V0 through Vx in the RPL user flags
+/// * Fx85 - LD Vx, R:       =>   populate V0 through Vx from the RPL user flags
+///
+/// # XO-CHIP Instructions
+/// * 00dn - SCU n:          =>   scroll the selected planes up n pixels
+/// * 5xy2 - LD [I], Vx - Vy:  => store Vx through Vy (in that order) at address starting at I
+/// * 5xy3 - LD Vx - Vy, [I]:  => starting from memory address I, populate Vx through Vy
+/// * F000 nnnn - LD I, long nnnn: => set I = nnnn, the instruction is 4 bytes long
+/// * Fn01 - PLANE n:        =>   select the bitplanes (bitmask n) drawing, clearing and scrolling use
+/// * F002 - AUDIO:          =>   load the 16 byte audio pattern starting at addr I
+/// * Fx3a - PITCH Vx:       =>   set the audio pattern playback pitch to Vx
+pub const INTERPRETER_END: u16 = 512;
 const FONT_SPRITES_MEM_START: u16 = 0;
-const SCREEN_WIDTH: u8 = 64;
-const SCREEN_HEIGHT: u8 = 32;
-const MEMORY_SIZE: u16 = 4096;
-const STACK_DEPTH: u8 = 16;
+const BIG_FONT_SPRITES_MEM_START: u16 = 0x50;
+/// Number of RPL user flags (V0 through VF)
+const RPL_FLAG_COUNT: u8 = 16;
+/// Number of bytes in an XO-CHIP audio pattern
+pub const AUDIO_PATTERN_LENGTH: usize = 16;
+/// XO-CHIP pitch at which the audio pattern plays at 4000 bits per second
+pub const DEFAULT_PITCH: u8 = 64;
+const MEMORY_SIZE: usize = 4096;
+const XO_MEMORY_SIZE: usize = 65536;
+/// Default number of return addresses the stack can hold
+pub const STACK_DEPTH: u8 = 16;
 const INSTRUCTION_WIDTH: u8 = 2;
-const MAX_SPRITE_LENGTH: u8 = 15;
-
-static DEFAULT_WINDOW_TITLE: &'static str = "pschip8";
-const DEFAULT_VIDEO_SCALE: u8 = 8;
+/// Number of keys on the hex keypad
+pub const KEY_COUNT: u8 = 16;
 
 const FONT_SPRITES: [u8; 80] = [
     0xf0, 0x90, 0x90, 0x90, 0xf0, // "0"
@@ -89,211 +106,612 @@ const FONT_SPRITES: [u8; 80] = [
     0xf0, 0x80, 0xf0, 0x80, 0x80, // "F"
 ];
 
-static DESIRED_AUDIO_SPEC: AudioSpecDesired = AudioSpecDesired {
-    freq: Some(44100),
-    channels: Some(1),
-    samples: Some(2048),
-};
-
-struct VideoSystem<'a> {
-    width: u8,
-    height: u8,
-    scale_factor: u8,
-    memory: Vec<bool>,
-    renderer: Renderer<'a>,
-    draw: bool,
+const BIG_FONT_SPRITES: [u8; 160] = [
+    0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, // "0"
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xff, 0xff, // "1"
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // "2"
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // "3"
+    0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0x03, 0x03, // "4"
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // "5"
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // "6"
+    0xff, 0xff, 0x03, 0x03, 0x06, 0x0c, 0x18, 0x18, 0x18, 0x18, // "7"
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // "8"
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // "9"
+    0x7e, 0xff, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, // "A"
+    0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, // "B"
+    0x3c, 0xff, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3, 0xff, 0x3c, // "C"
+    0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, // "D"
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // "E"
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0, // "F"
+];
+
+/// What happens when a CALL finds the stack full or a RET finds it empty
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum StackPolicy {
+    /// Stop with a `StackOverflow` or `StackUnderflow` error
+    #[default]
+    Trap,
+    /// Wrap the stack pointer around, overwriting (or returning to) the
+    /// entry at the other end of the stack
+    Wrap,
 }
 
-impl <'a> VideoSystem<'a> {
-    fn default(video_sys: &VideoSubsystem) -> Self {
-        let window = item_or_exit(video_sys.window(DEFAULT_WINDOW_TITLE,
-                            SCREEN_WIDTH as u32 * DEFAULT_VIDEO_SCALE as u32,
-                            SCREEN_HEIGHT as u32 * DEFAULT_VIDEO_SCALE as u32).build());
-
-        VideoSystem {
-            width: SCREEN_WIDTH,
-            height: SCREEN_HEIGHT,
-            scale_factor: DEFAULT_VIDEO_SCALE,
-            memory: vec![false; ((SCREEN_WIDTH as usize) * (SCREEN_HEIGHT as usize))],
-            renderer: item_or_exit(window.renderer().present_vsync().build()),
-            draw: true,
-        }
-    }
-
-    #[allow(unused)]
-    fn draw(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
-        let time_start = SystemTime::now();
-        let mut erased = false;
-        let sprite_len = sprite.len();
-
-        if (x >= self.width) || (y >= self.height) || (sprite_len as u8 > MAX_SPRITE_LENGTH) {
-            return erased;
-        }
-        let mut i = y;
-        while (i - y) < sprite_len as u8 && (i < self.height) {
-            let start = i as usize * self.width as usize + x as usize;
-            let vidlim = i as usize * self.width as usize + self.width as usize;
-
-            let mut j = start;
-            while (j < start + 8) && (j < vidlim) {
-                let shifts = (8 - (j - start)) - 1;
-                let prev = self.memory[j as usize];
-                let new = ((sprite[(i - y) as usize] >> shifts) & 0x1) == 1;
-                self.memory[j] = prev != new;
-                erased = if prev && new { true } else { erased };
-                j += 1;
-            }
-            i += 1
+/// Why `Interpreter::run_frame` stopped before running all its instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pause {
+    /// The program counter reached a breakpoint, the instruction at the
+    /// address has not run yet
+    Breakpoint(u16),
+    /// The instruction at `pc` touched `address`, which a watchpoint covers
+    Watchpoint { pc: u16, address: u16, access: Access },
+    /// The condition turned true after the last instruction
+    Condition(Condition),
+}
+
+/// Fx0A waiting for a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct KeyWait {
+    /// The register that receives the key
+    x: u8,
+    /// Keys held since the wait started, bit n for key n. A key only counts
+    /// once it is pressed again after being released.
+    held: u16,
+    /// The key pressed, waiting to be released with the `key_wait_release`
+    /// quirk
+    pressed: Option<u8>,
+}
+
+/// The headless chip-8 machine: the cpu, memory, stack, timers, keypad and
+/// framebuffer. It knows nothing about windows, audio devices or keyboards;
+/// a frontend feeds it key states, runs it frame by frame and presents its
+/// framebuffer.
+pub struct Interpreter {
+    cpu: Cpu,
+    memory: Vec<u8>,
+    stack: Vec<u16>,
+    stack_policy: StackPolicy,
+    quirks: Quirks,
+    delay_timer: u8,
+    sound_timer: u8,
+    keypad: [bool; KEY_COUNT as usize],
+    framebuffer: Framebuffer,
+    program: Vec<u8>,
+    halted: bool,
+    rpl_flags: [u8; RPL_FLAG_COUNT as usize],
+    rpl_path: Option<PathBuf>,
+    audio_pattern: Option<[u8; AUDIO_PATTERN_LENGTH]>,
+    pitch: u8,
+    seed: Option<u64>,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    /// The conditions and whether each held after the last instruction
+    conditions: Vec<(Condition, bool)>,
+    pause: Option<Pause>,
+    tracer: Option<Box<dyn Tracer>>,
+    key_wait: Option<KeyWait>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    /// Creates and initializes an interpreter
+    pub fn new() -> Interpreter {
+        let seed = SeededRandom::random_seed();
+        let mut interpreter = Interpreter {
+            cpu: Cpu::init(Box::new(SeededRandom::new(seed))),
+            memory: vec![0; MEMORY_SIZE],
+            stack: vec![0; STACK_DEPTH as usize],
+            stack_policy: StackPolicy::default(),
+            quirks: Quirks::default(),
+            delay_timer: 0,
+            sound_timer: 0,
+            keypad: [false; KEY_COUNT as usize],
+            framebuffer: Framebuffer::default(),
+            program: Vec::new(),
+            halted: false,
+            rpl_flags: [0; RPL_FLAG_COUNT as usize],
+            rpl_path: None,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            seed: Some(seed),
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+            pause: None,
+            tracer: None,
+            key_wait: None,
+        };
+        interpreter.load_fonts();
+        interpreter
+    }
+
+    /// Loads a program into the interpreter from the file pointed to by path argument
+    pub fn load_program_from_file(&mut self, path: &Path) -> Result<(), Chip8Error> {
+        let mut file = File::open(path)?;
+        let mut program = Vec::new();
+        file.read_to_end(&mut program)?;
+        self.load_program(program)
+    }
+
+    /// Loads a program into the interpreter from a slice of u16
+    pub fn load_from_bytes(&mut self, instructions: &[u16]) -> Result<(), Chip8Error> {
+        let mut program = Vec::with_capacity(instructions.len() * 2);
+        for instruction in instructions {
+            program.push(((instruction >> 8) & 0x00ffu16) as u8);
+            program.push((instruction & 0x00ffu16) as u8);
         }
-        self.draw = true;
-        let elapsed = SystemTime::now().duration_since(time_start).unwrap();
-        erased
+        self.load_program(program)
     }
 
-    fn render_screen(&mut self) {
-        if !self.draw {
-            return;
+    fn load_program(&mut self, program: Vec<u8>) -> Result<(), Chip8Error> {
+        let max = self.memory.len() - INTERPRETER_END as usize;
+        if program.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: program.len(), max });
         }
-        let _ = self.renderer.set_scale(self.scale_factor as f32, self.scale_factor as f32);
-        self.renderer.set_draw_color(Color::RGB(0, 0, 0));
-        self.renderer.clear();
+        self.program = program;
+        self.reset();
+        Ok(())
+    }
 
-        for (index, pixel) in self.memory.iter().enumerate() {
-            let y = index / self.width as usize;
-            let x = index - (y * self.width as usize);
+    fn load_fonts(&mut self) {
+        let start = FONT_SPRITES_MEM_START as usize;
+        self.memory[start..start + FONT_SPRITES.len()].copy_from_slice(&FONT_SPRITES);
+        let start = BIG_FONT_SPRITES_MEM_START as usize;
+        self.memory[start..start + BIG_FONT_SPRITES.len()].copy_from_slice(&BIG_FONT_SPRITES);
+    }
 
-            let color = if *pixel {
-                Color::RGB(255, 255, 255)
-            } else {
-                Color::RGB(0, 0, 0)
-            };
-            self.renderer.set_draw_color(color);
-            let _ = self.renderer.draw_point(Point::new(x as i32, y as i32));
+    /// Restarts the loaded program: memory is restored to its state right
+    /// after loading and the registers, stack, timers and screen are cleared.
+    pub fn reset(&mut self) {
+        self.cpu.registers = Default::default();
+        for byte in self.memory.iter_mut() {
+            *byte = 0;
         }
-        self.renderer.present();
-        self.draw = false;
+        self.load_fonts();
+        let start = INTERPRETER_END as usize;
+        self.memory[start..start + self.program.len()].copy_from_slice(&self.program);
+        for entry in self.stack.iter_mut() {
+            *entry = 0;
+        }
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.halted = false;
+        self.pause = None;
+        self.key_wait = None;
+        self.framebuffer.set_hires(false);
+        self.framebuffer.select_planes(0x1);
+        self.audio_pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        if let Some(seed) = self.seed {
+            self.cpu.set_random_source(Box::new(SeededRandom::new(seed)));
+        }
+        self.cpu.registers.pc = INTERPRETER_END;
     }
 
-    fn clear_screen(&mut self) {
-        for idx in 0..self.memory.len() {
-            self.memory[idx] = false;
-        }
+    /// Seeds the random numbers of Cxkk so that runs of a program with the
+    /// same seed and input are identical. The generator starts over from the
+    /// seed whenever the program is reset.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.cpu.set_random_source(Box::new(SeededRandom::new(seed)));
     }
-}
 
-struct Tone {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
+    /// The seed of the random numbers, None if they come from a source set
+    /// with `set_random_source`
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
 
-impl AudioCallback for Tone {
-    type Channel = f32;
+    /// Takes the random numbers of Cxkk from `source` instead of a seeded
+    /// generator, resetting the program does not restart the source
+    pub fn set_random_source(&mut self, source: Box<dyn RandomSource>) {
+        self.seed = None;
+        self.cpu.set_random_source(source);
+    }
 
-    fn callback(&mut self, out: &mut [f32]) {
-        for x in out.iter_mut() {
-            *x = match self.phase {
-                0.0...0.5 => self.volume,
-                _ => -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+    /// Serializes the complete machine: registers, memory, stack, timers,
+    /// screen, quirks, RPL flags, audio pattern and random number state. The
+    /// state can only be loaded into an interpreter running the same program.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new(&self.program);
+        for flag in &self.quirks.to_flags() {
+            state.bool(*flag);
         }
+        state.bool(self.stack_policy == StackPolicy::Wrap);
+
+        let registers = &self.cpu.registers;
+        for x in 0..16 {
+            state.u8(registers.get(x).unwrap());
+        }
+        state.u16(registers.i);
+        state.u16(registers.pc);
+        state.u8(registers.sp);
+        state.u8(self.stack.len() as u8);
+        for entry in &self.stack {
+            state.u16(*entry);
+        }
+        state.u8(self.delay_timer);
+        state.u8(self.sound_timer);
+        state.bool(self.halted);
+        state.block(&self.memory);
+
+        state.u8(self.framebuffer.width());
+        state.u8(self.framebuffer.height());
+        state.u8(self.framebuffer.planes());
+        state.block(self.framebuffer.pixels());
+
+        state.bytes(&self.rpl_flags);
+        state.bool(self.audio_pattern.is_some());
+        state.bytes(&self.audio_pattern.unwrap_or([0; AUDIO_PATTERN_LENGTH]));
+        state.u8(self.pitch);
+        state.option_u64(self.seed);
+        state.option_u64(self.cpu.random_source().save());
+
+        let wait = self.key_wait;
+        state.bool(wait.is_some());
+        state.u8(wait.map_or(0, |wait| wait.x));
+        state.u16(wait.map_or(0, |wait| wait.held));
+        state.bool(wait.and_then(|wait| wait.pressed).is_some());
+        state.u8(wait.and_then(|wait| wait.pressed).unwrap_or(0));
+        state.finish()
     }
-}
 
-struct SoundSystem {
-    au_dev: AudioDevice<Tone>,
-}
+    /// Restores a state written by `save_state`. The state is checked before
+    /// anything is changed, the machine is left as it was if it is rejected.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut state = StateReader::new(data, &self.program)?;
+        let mut flags = [false; QUIRK_COUNT];
+        for flag in flags.iter_mut() {
+            *flag = state.bool()?;
+        }
+        let quirks = Quirks::from_flags(&flags);
+        let stack_policy = if state.bool()? { StackPolicy::Wrap } else { StackPolicy::Trap };
 
-impl SoundSystem {
-    fn resume(&self) {
-        self.au_dev.resume();
+        let mut registers = Reg::default();
+        for x in 0..16 {
+            registers.set(x, state.u8()?);
+        }
+        registers.i = state.u16()?;
+        registers.pc = state.u16()?;
+        registers.sp = state.u8()?;
+        let depth = state.u8()?;
+        let mut stack = Vec::with_capacity(depth as usize);
+        for _ in 0..depth {
+            stack.push(state.u16()?);
+        }
+        if depth == 0 || registers.sp > depth {
+            return Err(state::invalid("the stack pointer is out of range"));
+        }
+        let delay_timer = state.u8()?;
+        let sound_timer = state.u8()?;
+        let halted = state.bool()?;
+        let memory = state.block()?;
+        let size = if quirks.large_memory { XO_MEMORY_SIZE } else { MEMORY_SIZE };
+        if memory.len() != size {
+            return Err(state::invalid("the memory size does not match the quirks"));
+        }
+
+        let width = state.u8()?;
+        let height = state.u8()?;
+        let planes = state.u8()?;
+        let pixels = state.block()?;
+        let mut framebuffer = Framebuffer::default();
+        if !framebuffer.restore(width, height, planes, pixels) {
+            return Err(state::invalid("the screen size is invalid"));
+        }
+
+        let mut rpl_flags = [0; RPL_FLAG_COUNT as usize];
+        rpl_flags.copy_from_slice(state.bytes(RPL_FLAG_COUNT as usize)?);
+        let has_pattern = state.bool()?;
+        let mut pattern = [0; AUDIO_PATTERN_LENGTH];
+        pattern.copy_from_slice(state.bytes(AUDIO_PATTERN_LENGTH)?);
+        let pitch = state.u8()?;
+        let seed = state.option_u64()?;
+        let rng_state = state.option_u64()?;
+        let waiting = state.bool()?;
+        let x = state.u8()?;
+        let held = state.u16()?;
+        let has_pressed = state.bool()?;
+        let pressed = state.u8()?;
+        let pressed = if has_pressed { Some(pressed) } else { None };
+        let mut key_wait = None;
+        if waiting {
+            if x > 0xf || pressed.is_some_and(|key| key >= KEY_COUNT) {
+                return Err(state::invalid("the key wait is invalid"));
+            }
+            key_wait = Some(KeyWait { x, held, pressed });
+        }
+
+        self.quirks = quirks;
+        self.stack_policy = stack_policy;
+        self.cpu.registers = registers;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.halted = halted;
+        self.memory = memory.to_vec();
+        self.framebuffer = framebuffer;
+        self.rpl_flags = rpl_flags;
+        self.audio_pattern = if has_pattern { Some(pattern) } else { None };
+        self.pitch = pitch;
+        self.key_wait = key_wait;
+        if let Some(seed) = seed {
+            self.set_seed(seed);
+        }
+        if let Some(rng_state) = rng_state {
+            self.cpu.random_source_mut().restore(rng_state);
+        }
+        Ok(())
+    }
+
+    /// Sets the number of return addresses the stack can hold (at least one)
+    /// and empties the stack
+    pub fn set_stack_depth(&mut self, depth: u8) {
+        self.stack = vec![0; ::std::cmp::max(depth, 1) as usize];
+        self.cpu.registers.sp = 0;
     }
 
-    fn pause(&self) {
-        self.au_dev.pause();
+    /// Sets what happens when the stack overflows or underflows
+    pub fn set_stack_policy(&mut self, policy: StackPolicy) {
+        self.stack_policy = policy;
     }
 
-    fn new(au_dev: AudioDevice<Tone>) -> Self {
-        SoundSystem {
-            au_dev: au_dev
+    /// Keeps the SUPER-CHIP RPL user flags in the file at `path`. The flags
+    /// are loaded from the file if it exists and written back to it every
+    /// time the program stores them.
+    pub fn set_rpl_path(&mut self, path: &Path) -> Result<(), Chip8Error> {
+        match File::open(path) {
+            Ok(mut file) => {
+                let mut flags = Vec::new();
+                file.read_to_end(&mut flags)?;
+                self.set_rpl_flags(&flags);
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(Chip8Error::Io(e)),
         }
+        self.rpl_path = Some(path.to_path_buf());
+        Ok(())
     }
-}
 
-#[allow(unused)]
-pub struct Interpreter<'a> {
-    cpu: Cpu,
-    memory: [u8; MEMORY_SIZE as usize],
-    stack: [u16; STACK_DEPTH as usize],
-    delay_timer: u8,
-    sound_timer: u8,
-    sdl: Sdl,
-    sound_system: SoundSystem,
-    video_system: VideoSystem<'a>,
-    event_pump: EventPump,
-}
+    /// The SUPER-CHIP RPL user flags
+    pub fn rpl_flags(&self) -> &[u8] {
+        &self.rpl_flags
+    }
 
-impl <'a> Interpreter<'a> {
-    /// Creates and initializes an interpreter
-    pub fn new() -> Interpreter<'a> {
-        let sdl_ctxt = item_or_exit(sdl2::init());
-        let au_sys = item_or_exit(sdl_ctxt.audio());
-        let vd_sys = item_or_exit(sdl_ctxt.video());
-        let evt_pump = item_or_exit(sdl_ctxt.event_pump());
+    /// Replaces the RPL user flags without writing them to the file set with
+    /// `set_rpl_path`. Extra flags are ignored, missing ones left alone.
+    pub fn set_rpl_flags(&mut self, flags: &[u8]) {
+        let len = ::std::cmp::min(flags.len(), self.rpl_flags.len());
+        self.rpl_flags[..len].copy_from_slice(&flags[..len]);
+    }
 
-        let mut interpreter = Interpreter {
-            cpu: Cpu::init(),
-            memory: [0; 4096],
-            stack: [0; 16],
-            delay_timer: 0,
-            sound_timer: 0,
-            sdl: sdl_ctxt,
-            sound_system: SoundSystem::new(item_or_exit(au_sys.open_playback(None, &DESIRED_AUDIO_SPEC, |spec| {
-                Tone {
-                    phase_inc: 440.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.5,
-                }
-            }))),
-            video_system: VideoSystem::default(&vd_sys),
-            event_pump: evt_pump,
-        };
-        for i in FONT_SPRITES_MEM_START..(FONT_SPRITES_MEM_START + FONT_SPRITES.len() as u16) {
-            interpreter.memory[i as usize] = FONT_SPRITES[(i - FONT_SPRITES_MEM_START) as usize];
+    /// Writes the RPL user flags to the file set with `set_rpl_path`, if any
+    fn save_rpl_flags(&self) -> Result<(), Chip8Error> {
+        if let Some(ref path) = self.rpl_path {
+            let mut file = File::create(path)?;
+            file.write_all(&self.rpl_flags)?;
         }
-        interpreter
+        Ok(())
     }
 
-    /// Loads a program into the interpreter from the file pointed to by path argument
-    pub fn load_program_from_file(&mut self, path: &Path) {
-        let mut file = item_or_exit(File::open(path));
-        let mut mem_idx = INTERPRETER_END as usize;
-        let mut buf = [0 as u8; 2];
-        loop {
-            let rc = item_or_exit(file.read(&mut buf));
-            if (mem_idx >= self.memory.len() - 1) || rc != 2 {
-                break;
+    /// The loaded program
+    pub fn program(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// Returns true once the program executed the SUPER-CHIP EXIT instruction
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Selects the platform behaviors to emulate. Changing the memory size
+    /// restarts the loaded program, and fails, leaving the quirks alone, if
+    /// the program doesn't fit in the smaller memory.
+    pub fn set_quirks(&mut self, quirks: Quirks) -> Result<(), Chip8Error> {
+        let size = if quirks.large_memory { XO_MEMORY_SIZE } else { MEMORY_SIZE };
+        if size != self.memory.len() {
+            let max = size - INTERPRETER_END as usize;
+            if self.program.len() > max {
+                return Err(Chip8Error::RomTooLarge { size: self.program.len(), max });
             }
-            self.memory[mem_idx] = buf[0];
-            self.memory[mem_idx + 1] = buf[1];
-            mem_idx += 2;
+            self.quirks = quirks;
+            self.memory = vec![0; size];
+            self.reset();
+        } else {
+            self.quirks = quirks;
         }
-        self.cpu.registers.pc = INTERPRETER_END;
+        Ok(())
     }
 
-    /// Loads a program into the interpreter from a slice of u16
-    pub fn load_from_bytes(&mut self, instructions: &[u16]) {
-        if instructions.len() > (MEMORY_SIZE - INTERPRETER_END) as usize {
-            panic!();
+    /// The platform behaviors being emulated
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// The return addresses currently on the stack, oldest first
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.cpu.registers.sp as usize]
+    }
+
+    /// Sets the stack pointer, the number of entries on the stack. Returns
+    /// false and leaves it alone if the stack is not that deep.
+    pub fn set_stack_pointer(&mut self, sp: u8) -> bool {
+        if sp as usize > self.stack.len() {
+            return false;
         }
-        let mut mem_idx = INTERPRETER_END as usize;
-        for instruction in instructions {
-            self.memory[mem_idx] = ((instruction >> 8) & 0x00ffu16) as u8;
-            self.memory[mem_idx + 1] = (instruction & 0x00ffu16) as u8;
-            mem_idx += 2;
+        self.cpu.registers.sp = sp;
+        true
+    }
+
+    /// The program counter
+    pub fn pc(&self) -> u16 {
+        self.cpu.registers.pc
+    }
+
+    /// Moves the program counter, the next instruction runs from `address`
+    pub fn set_pc(&mut self, address: u16) {
+        self.cpu.registers.pc = address;
+    }
+
+    /// The value of register Vx, None if x is above 0xf
+    pub fn register(&self, x: u8) -> Option<u8> {
+        self.cpu.registers.get(x)
+    }
+
+    /// Sets register Vx, x above 0xf is ignored
+    pub fn set_register(&mut self, x: u8, value: u8) {
+        self.cpu.registers.set(x, value);
+    }
+
+    /// The index register I
+    pub fn index(&self) -> u16 {
+        self.cpu.registers.i
+    }
+
+    /// Sets the index register I
+    pub fn set_index(&mut self, value: u16) {
+        self.cpu.registers.i = value;
+    }
+
+    /// The delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Sets the delay timer
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    /// The sound timer
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Sets the sound timer
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// The whole memory, 4 KiB or 64 KiB with the `large_memory` quirk
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Mutable access to the memory. Changes to the program are lost when it
+    /// is reset.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// Makes `run_frame` pause before running the instruction at `address`
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Removes a breakpoint, returns false if there was none at `address`
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Removes every breakpoint
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// The addresses with a breakpoint, in ascending order
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    /// Makes `run_frame` pause after an instruction touches memory the
+    /// watchpoint covers
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Removes and returns the watchpoint at `index` in `watchpoints`
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        if index < self.watchpoints.len() { Some(self.watchpoints.remove(index)) } else { None }
+    }
+
+    /// The watchpoints, in the order they were added
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Makes `run_frame` pause after an instruction that turns the condition
+    /// true. A condition that already holds waits until it turns false and
+    /// then true again.
+    pub fn add_condition(&mut self, condition: Condition) {
+        let holds = condition.holds(self);
+        self.conditions.push((condition, holds));
+    }
+
+    /// Removes and returns the condition at `index` in `conditions`
+    pub fn remove_condition(&mut self, index: usize) -> Option<Condition> {
+        if index < self.conditions.len() { Some(self.conditions.remove(index).0) } else { None }
+    }
+
+    /// The conditions, in the order they were added
+    pub fn conditions(&self) -> Vec<Condition> {
+        self.conditions.iter().map(|entry| entry.0).collect()
+    }
+
+    /// Hands an entry to `tracer` after every instruction from now on, None
+    /// stops tracing
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Tracer>>) {
+        self.tracer = tracer;
+    }
+
+    /// Why the last `run_frame` stopped early, None if it ran to the end of
+    /// the frame
+    pub fn pause(&self) -> Option<Pause> {
+        self.pause
+    }
+
+    /// The screen contents
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// Mutable access to the screen, used by frontends to mark it as presented
+    pub fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    /// Sets the state of a key on the hex keypad, keys above 0xf are ignored
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if key < KEY_COUNT {
+            self.keypad[key as usize] = pressed;
         }
-        self.cpu.registers.pc = INTERPRETER_END;
+    }
+
+    /// Returns true if the key on the hex keypad is held down
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        key < KEY_COUNT && self.keypad[key as usize]
+    }
+
+    /// Returns true while Fx0A waits for a key. The program counter stays on
+    /// the Fx0A and `run_frame` ends the frame early, only ticking the timers.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
+    /// Returns true while the sound timer is running, i.e. the tone should play
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// The XO-CHIP audio pattern (a 128 bit, 1 bit per sample waveform)
+    /// loaded by F002 and the pitch set by Fx3a, or None if the program never
+    /// loaded a pattern and expects the plain tone
+    pub fn audio_pattern(&self) -> Option<(&[u8; AUDIO_PATTERN_LENGTH], u8)> {
+        self.audio_pattern.as_ref().map(|pattern| (pattern, self.pitch))
     }
 
     /// Prints the contents of the interpreter's memory
@@ -304,13 +722,13 @@ impl <'a> Interpreter<'a> {
         loop {
             print!("{}: {}", lidx, self.memory[lidx]);
             lidx += 1;
-            if lidx >= MEMORY_SIZE as usize {
+            if lidx >= self.memory.len() {
                 break;
             } else {
                 print!(", ");
             }
             if lidx >= 8 && lidx % 8 == 0 {
-                println!("");
+                println!();
             }
         }
         println!("\n");
@@ -326,7 +744,7 @@ impl <'a> Interpreter<'a> {
                self.cpu.registers.v4, self.cpu.registers.v5,
                self.cpu.registers.v6, self.cpu.registers.v7);
         println!("V8: {}, V9: {}, Va: {}, Vb: {}",
-               self.cpu.registers.v9, self.cpu.registers.v9,
+               self.cpu.registers.v8, self.cpu.registers.v9,
                self.cpu.registers.va, self.cpu.registers.vb);
         println!("Vc: {}, Vd: {}, Ve: {}, Vf: {}",
                self.cpu.registers.vc, self.cpu.registers.vd,
@@ -334,518 +752,492 @@ impl <'a> Interpreter<'a> {
         println!("i: {}, pc: {}, sp: {}",
                  self.cpu.registers.i, self.cpu.registers.pc,
                  self.cpu.registers.sp);
-        println!("");
-    }
-
-    /// Executes a single instruction (retrieved via fetch)
-    fn cycle(&mut self) {
-        let instruction = self.fetch();
-        let opcode = ((instruction & 0xf000u16) >> 12) as u8;
-
-        //println!("[DEBUG]  About to execute: 0x{:x}", instruction);
-        //self.print_registers();
-
-        match opcode {
-            0x0 => {
-                let lnnn = instruction & 0x0fffu16;
-                // clear screen
-                if lnnn == 0x00e0 {
-                    self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                    self.video_system.clear_screen();
-
-                // return from subroutine
-                } else if lnnn == 0x00ee {
-                    self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                    let sp = match self.cpu.registers.sp {
-                        0 => 0,
-                        n @ 1...15 => {
-                            self.cpu.registers.sp -= 1;
-                            n - 1
-                        },
-                        _ => 0
-                    };
-                    self.cpu.registers.pc = self.stack[sp as usize];
-                } else {
-                    self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                }
+        println!();
+    }
+
+    /// Executes `cycles` instructions and then updates the timers once.
+    ///
+    /// Reaching a breakpoint pauses the frame before the instruction runs,
+    /// a watchpoint or condition right after it, see `pause`. A paused frame
+    /// leaves the timers alone and the next call carries on from there.
+    pub fn run_frame(&mut self, cycles: usize) -> Result<(), Chip8Error> {
+        let resumed = self.pause.take();
+        for cycle in 0..cycles {
+            if self.halted {
+                break;
+            }
+            let pc = self.cpu.registers.pc;
+            let resuming = cycle == 0 && resumed == Some(Pause::Breakpoint(pc));
+            // a key wait sits on its Fx0A, which was already stopped at
+            if self.breakpoints.contains(&pc) && !resuming && self.key_wait.is_none() {
+                self.pause = Some(Pause::Breakpoint(pc));
+                return Ok(());
+            }
+            self.step()?;
+            if self.pause.is_some() {
+                return Ok(());
+            }
+            // nothing more to do until the keypad changes, next frame
+            if self.key_wait.is_some() {
+                break;
+            }
+        }
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// Executes a single instruction (retrieved via fetch), does nothing once
+    /// the interpreter is halted. While Fx0A waits for a key it checks the
+    /// keypad instead, see `is_waiting_for_key`. Breakpoints don't stop it,
+    /// but `pause` tells if a watchpoint or condition went off.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        self.pause = None;
+        if self.halted {
+            return Ok(());
+        }
+        if let Some(wait) = self.key_wait {
+            return self.wait_for_key(wait);
+        }
+        let address = self.cpu.registers.pc;
+        let opcode = self.fetch()?;
+        let before = self.tracer.as_ref().map(|_| self.v_registers());
+        let result = match Instruction::decode(opcode) {
+            Ok(instruction) => {
+                self.advance_pc();
+                self.execute(address, instruction)
             },
-            0x1 => {
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
+            Err(_) => Err(Chip8Error::InvalidOpcode { address, opcode }),
+        };
+        if result.is_ok() {
+            self.check_conditions();
+        }
+        // an instruction that fails is traced too, so a crash dump ends with it
+        if let Some(before) = before {
+            self.trace(address, opcode, before)?;
+        }
+        result
+    }
 
-                // jmp nnn
-                let nnn = instruction & 0x0fff;
-                self.cpu.registers.pc = nnn;
+    /// Checks the keypad for the Fx0A waiting at the program counter. A key
+    /// pressed since the wait started completes it, or with the
+    /// `key_wait_release` quirk its release does. The key goes in Vx and the
+    /// program carries on after the Fx0A.
+    fn wait_for_key(&mut self, mut wait: KeyWait) -> Result<(), Chip8Error> {
+        let keys = self.keypad_mask();
+        let key = match wait.pressed {
+            Some(key) if keys & (1 << key) == 0 => key,
+            Some(_) => return Ok(()),
+            None => {
+                // keys held when the wait started count once released
+                wait.held &= keys;
+                let pressed = keys & !wait.held;
+                if pressed == 0 {
+                    self.key_wait = Some(wait);
+                    return Ok(());
+                }
+                let key = pressed.trailing_zeros() as u8;
+                if self.quirks.key_wait_release {
+                    wait.pressed = Some(key);
+                    self.key_wait = Some(wait);
+                    return Ok(());
+                }
+                key
             },
-            0x2 => {
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
+        };
+
+        let address = self.cpu.registers.pc;
+        let before = self.tracer.as_ref().map(|_| self.v_registers());
+        self.key_wait = None;
+        self.set_v(wait.x, key);
+        self.advance_pc();
+        self.check_conditions();
+        if let Some(before) = before {
+            self.trace(address, 0xf00a | (wait.x as u16) << 8, before)?;
+        }
+        Ok(())
+    }
 
-                // CALL addr:nnn
-                let nnn = instruction & 0x0fff;
+    /// The keys held, bit n for key n
+    fn keypad_mask(&self) -> u16 {
+        (0..KEY_COUNT).filter(|key| self.keypad[*key as usize]).fold(0, |keys, key| keys | 1 << key)
+    }
+
+    /// Executes a decoded instruction, the program counter already points past
+    /// its opcode
+    fn execute(&mut self, address: u16, instruction: Instruction) -> Result<(), Chip8Error> {
+        use instruction::Instruction::*;
+
+        match instruction {
+            Sys(_) => {},
+            Cls => self.framebuffer.clear(),
+            Ret => {
+                if self.cpu.registers.sp == 0 {
+                    match self.stack_policy {
+                        StackPolicy::Trap => {
+                            return Err(Chip8Error::StackUnderflow { pc: address, stack: Vec::new() });
+                        },
+                        StackPolicy::Wrap => self.cpu.registers.sp = self.stack.len() as u8,
+                    }
+                }
+                self.cpu.registers.sp -= 1;
+                self.cpu.registers.pc = self.stack[self.cpu.registers.sp as usize];
+            },
+            Jp(nnn) => self.cpu.registers.pc = nnn,
+            Call(nnn) => {
+                if self.cpu.registers.sp as usize >= self.stack.len() {
+                    match self.stack_policy {
+                        StackPolicy::Trap => {
+                            return Err(Chip8Error::StackOverflow { pc: address, stack: self.call_stack().to_vec() });
+                        },
+                        StackPolicy::Wrap => self.cpu.registers.sp = 0,
+                    }
+                }
                 self.stack[self.cpu.registers.sp as usize] = self.cpu.registers.pc;
-                self.cpu.registers.sp +=  1 % (STACK_DEPTH - 1);
+                self.cpu.registers.sp += 1;
                 self.cpu.registers.pc = nnn;
             },
-            0x3 => {
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-
-                // 3xkk - SE Vx, byte
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let kk = (instruction & 0x00ffu16) as u8;
-
-                let vx = self.cpu.registers.get(x).unwrap();
-                if vx == kk { self.cpu.registers.pc += INSTRUCTION_WIDTH as u16 }
+            SeByte(x, kk) => self.skip_if(self.v(x) == kk),
+            SneByte(x, kk) => self.skip_if(self.v(x) != kk),
+            SeReg(x, y) => self.skip_if(self.v(x) == self.v(y)),
+            LdByte(x, kk) => self.set_v(x, kk),
+            AddByte(x, kk) => {
+                let vx = self.v(x);
+                self.set_v(x, vx.wrapping_add(kk));
+            },
+            LdReg(x, y) => {
+                let vy = self.v(y);
+                self.set_v(x, vy);
             },
-            0x4 => {
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                // 4xkk - SNE Vx, byte
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let kk = (instruction & 0x00ffu16) as u8;
-
-                let vx = self.cpu.registers.get(x).unwrap();
-                if vx != kk { self.cpu.registers.pc += INSTRUCTION_WIDTH as u16 }
+            Or(x, y) => self.logic(x, y, |vx, vy| vx | vy),
+            And(x, y) => self.logic(x, y, |vx, vy| vx & vy),
+            Xor(x, y) => self.logic(x, y, |vx, vy| vx ^ vy),
+            AddReg(x, y) => {
+                let (result, carry) = self.v(x).overflowing_add(self.v(y));
+                self.set_v(x, result);
+                self.cpu.registers.vf = carry as u8;
             },
-            0x5 => {
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                // 4xy0 - SE Vx, Vy
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let y = (instruction >> 4u16 & 0x000fu16) as u8;
-                let vx = self.cpu.registers.get(x).unwrap();
-                let vy = self.cpu.registers.get(y).unwrap();
-                if vx == vy { self.cpu.registers.pc += INSTRUCTION_WIDTH as u16 }
+            Sub(x, y) => {
+                let (result, borrow) = self.v(x).overflowing_sub(self.v(y));
+                self.set_v(x, result);
+                self.cpu.registers.vf = !borrow as u8;
             },
-            0x6 => {
-                // 6xkk - LD Vx, byte
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let kk = (instruction & 0x00ffu16) as u8;
-                self.cpu.registers.set(x, kk);
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
+            Shr(x, y) => {
+                let src = if self.quirks.shift_uses_vy { y } else { x };
+                let value = self.v(src);
+                self.set_v(x, value >> 1);
+                self.cpu.registers.vf = value & 0x01;
             },
-            0x7 => {
-                // 7xkk - ADD Vx, byte
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let kk = (instruction & 0x00ffu16) as usize;
-                let vx = self.cpu.registers.get(x).unwrap() as usize;
-
-                // check if addition causes u8 overflow
-                if (vx + kk) > 255 {
-                    self.cpu.registers.vf = 1;
+            Subn(x, y) => {
+                let (result, borrow) = self.v(y).overflowing_sub(self.v(x));
+                self.set_v(x, result);
+                self.cpu.registers.vf = !borrow as u8;
+            },
+            Shl(x, y) => {
+                let src = if self.quirks.shift_uses_vy { y } else { x };
+                let value = self.v(src);
+                self.set_v(x, value << 1);
+                self.cpu.registers.vf = value >> 7;
+            },
+            SneReg(x, y) => self.skip_if(self.v(x) != self.v(y)),
+            LdI(nnn) => self.cpu.registers.i = nnn,
+            JpV0(nnn) => {
+                let reg = if self.quirks.jump_uses_vx { (nnn >> 8) as u8 } else { 0 };
+                self.cpu.registers.pc = nnn + self.v(reg) as u16;
+            },
+            Rnd(x, kk) => {
+                let random = self.cpu.random_byte();
+                self.set_v(x, random & kk);
+            },
+            Drw(x, y, n) => {
+                let vx = self.v(x);
+                let vy = self.v(y);
+                let i = self.cpu.registers.i as usize;
+                // there is sprite data for each selected XO-CHIP plane
+                let planes = self.framebuffer.selected_plane_count() as usize;
+                let collision = if n == 0 {
+                    // Dxy0 - DRW Vx, Vy, 0
+                    let len = LARGE_SPRITE_LENGTH as usize * planes;
+                    self.access(address, Access::Read, i, len)?;
+                    self.framebuffer.draw_large(vx, vy, &self.memory[i..i + len], self.quirks.wrap_sprites)
+                } else {
+                    let len = n as usize * planes;
+                    self.access(address, Access::Read, i, len)?;
+                    self.framebuffer.draw(vx, vy, &self.memory[i..i + len], self.quirks.wrap_sprites)
+                };
+                self.cpu.registers.vf = if self.quirks.collision_counts_rows && self.framebuffer.is_hires() {
+                    collision.rows + collision.clipped
                 } else {
-                    self.cpu.registers.vf = 0;
+                    collision.erased() as u8
+                };
+            },
+            Skp(x) => self.skip_if(self.is_key_pressed(self.v(x))),
+            Sknp(x) => self.skip_if(!self.is_key_pressed(self.v(x))),
+            LdVxDt(x) => {
+                let delay = self.delay_timer;
+                self.set_v(x, delay);
+            },
+            LdVxK(x) => {
+                // the program counter stays on the instruction until a key
+                // completes the wait, see `wait_for_key`
+                self.key_wait = Some(KeyWait { x, held: self.keypad_mask(), pressed: None });
+                self.cpu.registers.pc = address;
+            },
+            LdDtVx(x) => self.delay_timer = self.v(x),
+            LdStVx(x) => self.sound_timer = self.v(x),
+            AddI(x) => self.cpu.registers.i = self.cpu.registers.i.wrapping_add(self.v(x) as u16),
+            LdF(x) => {
+                let vx = self.v(x) as u16;
+                if vx <= 0xf {
+                    self.cpu.registers.i = FONT_SPRITES_MEM_START + (vx * 5);
                 }
-                self.cpu.registers.set(x, (vx + kk) as u8);
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
             },
-            0x8 => {
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let y = (instruction >> 4u16 & 0x000fu16) as u8;
-                let n = (instruction & 0x000fu16) as u8;
-
-                match n {
-                    // 8xy0 - LD Vx, Vy
-                    0x0 => {
-                        let vy = self.cpu.registers.get(y).unwrap();
-                        self.cpu.registers.set(x, vy);
-                    },
-                    // 8xy1 - OR Vx, Vy
-                    0x1 => {
-                        let vx = self.cpu.registers.get(x).unwrap();
-                        let vy = self.cpu.registers.get(y).unwrap();
-                        self.cpu.registers.set(x, vx | vy);
-                    },
-                    // 8xy2 - AND Vx, Vy
-                    0x2 => {
-                        let vx = self.cpu.registers.get(x).unwrap();
-                        let vy = self.cpu.registers.get(y).unwrap();
-                        self.cpu.registers.set(x, vx & vy);
-                    },
-                    // 8xy3 - XOR Vx, Vy
-                    0x3 => {
-                        let vx = self.cpu.registers.get(x).unwrap();
-                        let vy = self.cpu.registers.get(y).unwrap();
-                        self.cpu.registers.set(x, vx ^ vy);
-                    },
-                    // 8xy4 - ADD Vx, Vy
-                    0x4 => {
-                        let vx = self.cpu.registers.get(x).unwrap() as usize;
-                        let vy = self.cpu.registers.get(y).unwrap() as usize;
-
-                        if (vx + vy) > 255 {
-                            self.cpu.registers.vf = 1;
-                        } else {
-                            self.cpu.registers.vf = 0;
-                        }
-                        self.cpu.registers.set(x, (vx + vy) as u8);
-                    },
-                    // 8xy5 - SUB Vx, Vy
-                    0x5 => {
-                        let vx = self.cpu.registers.get(x).unwrap() as usize;
-                        let vy = self.cpu.registers.get(y).unwrap() as usize;
-
-                        if vx > vy {
-                            self.cpu.registers.vf = 1;
-                            self.cpu.registers.set(x, (vx - vy) as u8);
-                        } else {
-                            self.cpu.registers.vf = 0;
-                            self.cpu.registers.set(x, 0);
-                        }
-                    },
-                    // 8xy6 - SHR Vx {, Vy}
-                    0x6 => {
-                        let vx = self.cpu.registers.get(x).unwrap();
-                        let vy = self.cpu.registers.get(x).unwrap();
-                        self.cpu.registers.vf = vx & 0x01;
-                        self.cpu.registers.set(x, ((vy as usize) >> 1) as u8);
-                    },
-                    // 8xy7 - SUBN Vx ,Vy
-                    0x7 => {
-                        let vx = self.cpu.registers.get(x).unwrap() as usize;
-                        let vy = self.cpu.registers.get(y).unwrap() as usize;
-
-                        if vx < vy {
-                            self.cpu.registers.vf = 1;
-                            self.cpu.registers.set(x, (vy - vx) as u8);
-                        } else {
-                            self.cpu.registers.vf = 0;
-                            self.cpu.registers.set(x, 0);
-                        }
-                    },
-                    // 8xy6 - SHL Vx {, Vy}
-                    0xe => {
-                        let vx = self.cpu.registers.get(x).unwrap();
-                        let vy = self.cpu.registers.get(x).unwrap();
-                        self.cpu.registers.vf = vx & 0x10;
-                        self.cpu.registers.set(x, ((vy as usize) << 1) as u8);
-                    },
-                    _ => { }
+            LdB(x) => {
+                let vx_bcd = u8_to_bcd(self.v(x));
+                let ireg = self.cpu.registers.i as usize;
+                self.access(address, Access::Write, ireg, 3)?;
+                self.memory[ireg..ireg + 3].copy_from_slice(&vx_bcd);
+            },
+            LdMemVx(x) => {
+                let targets = self.copy_addresses(address, Access::Write, x as usize + 1)?;
+                for (i, at) in targets.into_iter().enumerate() {
+                    self.memory[at] = self.v(i as u8);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.cpu.registers.i = self.cpu.registers.i.wrapping_add(x as u16 + 1);
                 }
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
             },
-            0x9 => {
-                // 9xy0 - SNE Vx, Vy
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let y = (instruction >> 4u16 & 0x000fu16) as u8;
-                let vx = self.cpu.registers.get(x).unwrap();
-                let vy = self.cpu.registers.get(y).unwrap();
-                if vx != vy { self.cpu.registers.pc += INSTRUCTION_WIDTH as u16 }
+            LdVxMem(x) => {
+                let sources = self.copy_addresses(address, Access::Read, x as usize + 1)?;
+                for (i, at) in sources.into_iter().enumerate() {
+                    let mem_val = self.memory[at];
+                    self.set_v(i as u8, mem_val);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.cpu.registers.i = self.cpu.registers.i.wrapping_add(x as u16 + 1);
+                }
             },
-            0xa => {
-                // Annn - LD I, addr
-                let nnn = instruction & 0x0fff;
-                self.cpu.registers.i = nnn;
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
+            Scd(n) => self.framebuffer.scroll_down(n),
+            Scr => self.framebuffer.scroll_right(4),
+            Scl => self.framebuffer.scroll_left(4),
+            Exit => self.halted = true,
+            Low | High => {
+                let hires = instruction == High;
+                if self.quirks.resolution_change_clears {
+                    self.framebuffer.set_hires(hires);
+                } else {
+                    self.framebuffer.resize(hires);
+                }
             },
-            0xb => {
-                // Bnnn - JP V0, addr
-                let nnn = instruction & 0x0fff;
-                let v0 = self.cpu.registers.v0;
-                self.cpu.registers.pc = nnn + (v0 as u16);
+            LdHf(x) => {
+                let vx = self.v(x) as u16;
+                if vx <= 0xf {
+                    self.cpu.registers.i = BIG_FONT_SPRITES_MEM_START + (vx * 10);
+                }
             },
-            0xc => {
-                // Cxkk - RND Vx, byte
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let kk = (instruction & 0x00ffu16) as u8;
-                let random = self.cpu.random_byte();
-                self.cpu.registers.set(x, random & kk);
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
+            LdRVx(x) => {
+                for i in 0..(x + 1) {
+                    self.rpl_flags[i as usize] = self.v(i);
+                }
+                self.save_rpl_flags()?;
             },
-            0xd => {
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                // Dxyn - DRW Vx, Vy, nibble
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let y = (instruction >> 4u16 & 0x000fu16) as u8;
-                let n = instruction & 0x000fu16;
-                let i = self.cpu.registers.i;
-                let sprite = &self.memory[(i as usize..(i+n) as usize)];
-                let erased = self.video_system.draw(self.cpu.registers.get(x).unwrap_or(0), self.cpu.registers.get(y).unwrap_or(0), sprite);
-                self.cpu.registers.vf = if erased { 1 } else { 0 };
+            LdVxR(x) => {
+                for i in 0..(x + 1) {
+                    let flag = self.rpl_flags[i as usize];
+                    self.set_v(i, flag);
+                }
             },
-            0xe => {
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let kk = (instruction & 0x00ffu16) as u8;
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-
-                match kk {
-                    // Ex9e - SKP Vx
-                    0x9e => {
-                        let mut skip = false;
-                        let reg_value = self.cpu.registers.get(x).unwrap();
-                        self.event_pump.pump_events();
-                        let keyboard_state = self.event_pump.keyboard_state();
-
-                        let pressed_keys: HashSet<Scancode> = keyboard_state.pressed_scancodes().collect();
-                        match reg_value {
-                            0 => { if pressed_keys.contains(&Scancode::Num0) || pressed_keys.contains(&Scancode::Kp0) { skip = true } },
-                            1 => { if pressed_keys.contains(&Scancode::Num1) || pressed_keys.contains(&Scancode::Kp1) { skip = true } },
-                            2 => { if pressed_keys.contains(&Scancode::Num2) || pressed_keys.contains(&Scancode::Kp2) { skip = true } },
-                            3 => { if pressed_keys.contains(&Scancode::Num3) || pressed_keys.contains(&Scancode::Kp3) { skip = true } },
-                            4 => { if pressed_keys.contains(&Scancode::Num4) || pressed_keys.contains(&Scancode::Kp4) { skip = true } },
-                            5 => { if pressed_keys.contains(&Scancode::Num5) || pressed_keys.contains(&Scancode::Kp5) { skip = true } },
-                            6 => { if pressed_keys.contains(&Scancode::Num6) || pressed_keys.contains(&Scancode::Kp6) { skip = true } },
-                            7 => { if pressed_keys.contains(&Scancode::Num7) || pressed_keys.contains(&Scancode::Kp7) { skip = true } },
-                            8 => { if pressed_keys.contains(&Scancode::Num8) || pressed_keys.contains(&Scancode::Kp8) { skip = true } },
-                            9 => { if pressed_keys.contains(&Scancode::Num9) || pressed_keys.contains(&Scancode::Kp9) { skip = true } },
-                            0xa => { if pressed_keys.contains(&Scancode::A) { skip = true } },
-                            0xb => { if pressed_keys.contains(&Scancode::B) { skip = true } },
-                            0xc => { if pressed_keys.contains(&Scancode::C) { skip = true } },
-                            0xd => { if pressed_keys.contains(&Scancode::D) { skip = true } },
-                            0xe => { if pressed_keys.contains(&Scancode::E) { skip = true } },
-                            0xf => { if pressed_keys.contains(&Scancode::F) { skip = true } },
-                            _ => {}
-                        }
-
-                        if skip {
-                            self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                        }
-                    },
-                    // Exa1 - SKNP Vx
-                    0xa1 => {
-                        let mut skip = true;
-                        let reg_value = self.cpu.registers.get(x).unwrap();
-                        self.event_pump.pump_events();
-                        let keyboard_state = self.event_pump.keyboard_state();
-
-                        let pressed_keys: HashSet<Scancode> = keyboard_state.pressed_scancodes().collect();
-                        match reg_value {
-                            0 => { if pressed_keys.contains(&Scancode::Num0) || pressed_keys.contains(&Scancode::Kp0) { skip = false } },
-                            1 => { if pressed_keys.contains(&Scancode::Num1) || pressed_keys.contains(&Scancode::Kp1) { skip = false } },
-                            2 => { if pressed_keys.contains(&Scancode::Num2) || pressed_keys.contains(&Scancode::Kp2) { skip = false } },
-                            3 => { if pressed_keys.contains(&Scancode::Num3) || pressed_keys.contains(&Scancode::Kp3) { skip = false } },
-                            4 => { if pressed_keys.contains(&Scancode::Num4) || pressed_keys.contains(&Scancode::Kp4) { skip = false } },
-                            5 => { if pressed_keys.contains(&Scancode::Num5) || pressed_keys.contains(&Scancode::Kp5) { skip = false } },
-                            6 => { if pressed_keys.contains(&Scancode::Num6) || pressed_keys.contains(&Scancode::Kp6) { skip = false } },
-                            7 => { if pressed_keys.contains(&Scancode::Num7) || pressed_keys.contains(&Scancode::Kp7) { skip = false } },
-                            8 => { if pressed_keys.contains(&Scancode::Num8) || pressed_keys.contains(&Scancode::Kp8) { skip = false } },
-                            9 => { if pressed_keys.contains(&Scancode::Num9) || pressed_keys.contains(&Scancode::Kp9) { skip = false } },
-                            0xa => { if pressed_keys.contains(&Scancode::A) { skip = false } },
-                            0xb => { if pressed_keys.contains(&Scancode::B) { skip = false } },
-                            0xc => { if pressed_keys.contains(&Scancode::C) { skip = false } },
-                            0xd => { if pressed_keys.contains(&Scancode::D) { skip = false } },
-                            0xe => { if pressed_keys.contains(&Scancode::E) { skip = false } },
-                            0xf => { if pressed_keys.contains(&Scancode::F) { skip = false } },
-                            _ => {}
-                        }
-
-                        if skip {
-                            self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-                        }
-                    },
-                    _ => { }
+            Scu(n) => self.framebuffer.scroll_up(n),
+            LdMemRange(x, y) => {
+                let regs = register_range(x, y);
+                let targets = self.copy_addresses(address, Access::Write, regs.len())?;
+                for (reg, at) in regs.into_iter().zip(targets) {
+                    self.memory[at] = self.v(reg);
                 }
             },
-            0xf => {
-                let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                let kk = (instruction & 0x00ffu16) as u8;
-                self.cpu.registers.pc += INSTRUCTION_WIDTH as u16;
-
-                match kk {
-                    // Fx07 - LD Vx, DT
-                    0x07 => {
-                        self.cpu.registers.set(x, self.delay_timer);
-                    },
-                    // Fx0a - LD Vx, K
-                    0x0a => {
-                        'event_loop: loop {
-                            let event = self.event_pump.wait_event();
-                            match event {
-                                Event::KeyDown{keycode: kc, keymod: km, ..} => {
-                                    match kc {
-                                        Some(Keycode::Num0) | Some(Keycode::Kp0) => {
-                                            self.cpu.registers.set(x, 0);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num1) | Some(Keycode::Kp1) => {
-                                            self.cpu.registers.set(x, 1);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num2) | Some(Keycode::Kp2) => {
-                                            self.cpu.registers.set(x, 2);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num3) | Some(Keycode::Kp3) => {
-                                            self.cpu.registers.set(x, 3);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num4) | Some(Keycode::Kp4) => {
-                                            self.cpu.registers.set(x, 4);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num5) | Some(Keycode::Kp5) => {
-                                            self.cpu.registers.set(x, 5);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num6) | Some(Keycode::Kp6) => {
-                                            self.cpu.registers.set(x, 6);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num7) | Some(Keycode::Kp7) => {
-                                            self.cpu.registers.set(x, 7);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num8) | Some(Keycode::Kp8) => {
-                                            self.cpu.registers.set(x, 8);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::Num9) | Some(Keycode::Kp9) => {
-                                            self.cpu.registers.set(x, 9);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::A) => {
-                                            self.cpu.registers.set(x, 0xa);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::B) => {
-                                            self.cpu.registers.set(x, 0xb);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::C) => {
-                                            self.cpu.registers.set(x, 0xc);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::D) => {
-                                            self.cpu.registers.set(x, 0xd);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::E) => {
-                                            self.cpu.registers.set(x, 0xe);
-                                            break 'event_loop
-                                        },
-                                        Some(Keycode::F) => {
-                                            self.cpu.registers.set(x, 0xf);
-                                            break 'event_loop
-                                        },
-                                        // possible interpreter restart
-                                        Some(Keycode::R) => {
-                                            if km.contains(keyboard::LSHIFTMOD) ||
-                                               km.contains(keyboard::RSHIFTMOD) {
-                                                self.cpu.registers.pc = 0;
-                                                self.video_system.clear_screen();
-                                                return;
-                                            }
-                                        },
-                                        // If the keycode does not match [0-9a-f] continue the loop
-                                        _ => {}
-                                    }
-                                },
-                                Event::Quit{..} => {
-                                    process::exit(0);
-                                },
-                                // If the event is not a keydown event, continue the loop
-                                _ => {}
-                            }
-                        }
-                    },
-                    // Fx15 - LD  DT, Vx
-                    0x15 => {
-                        self.delay_timer = self.cpu.registers.get(x).unwrap();
-                    },
-                    // Fx18 - LD ST, Vx
-                    0x18 => {
-                        self.sound_timer = self.cpu.registers.get(x).unwrap();
-                    },
-                    // Fx1e - ADD I, Vx
-                    0x1e => {
-                        let regv = self.cpu.registers.get(x).unwrap();
-                        self.cpu.registers.i += regv as u16;
-                    },
-                    // Fx29 - LD F, Vx
-                    0x29 => {
-                        let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                        let vx = self.cpu.registers.get(x).unwrap() as u16;
-                        if vx <= 0xf {
-                            self.cpu.registers.i = FONT_SPRITES_MEM_START + (vx as u16 * 5);
-                        }
-                    },
-                    // Fx33 - LD B, Vx
-                    0x33 => {
-                        let x = ((instruction >> 8u16) & 0x000fu16) as u8;
-                        let vx = self.cpu.registers.get(x).unwrap();
-                        let vx_bcd = u8_to_bcd(vx);
-                        let ireg = self.cpu.registers.i as usize;
-
-                        for i in 0..3 {
-                            self.memory[ireg + i] = vx_bcd[i];
-                        }
-                    },
-                    // Fx55 - LD [I], Vx
-                    0x55 => {
-                        let ireg = self.cpu.registers.i;
-                        for i in 0..(x + 1) {
-                            let regv = self.cpu.registers.get(i).unwrap();
-                            self.memory[(ireg + i as u16) as usize] = regv;
-                        }
-                    },
-                    // Fx65 - LD Vx, [I]
-                    0x65 => {
-                        let ireg = self.cpu.registers.i;
-                        for i in 0..(x + 1) {
-                            let mem_val = self.memory[(ireg + i as u16) as usize];
-                            self.cpu.registers.set(i, mem_val as u8);
-                        }
-                    },
-                    _ => { }
+            LdRangeMem(x, y) => {
+                let regs = register_range(x, y);
+                let sources = self.copy_addresses(address, Access::Read, regs.len())?;
+                for (reg, at) in regs.into_iter().zip(sources) {
+                    let mem_val = self.memory[at];
+                    self.set_v(reg, mem_val);
                 }
             },
-            _ => {
-                // This should never happen
-                panic!("The end is near!");
-            }
-        }
-    }
-
-    /// After initializing the interpreter, this method should be called to start
-    /// running
-    pub fn run(&mut self) {
-        // nanoseconds per frame
-        let spf_nano = Duration::new(0, 1_000_000);
-        loop {
-            self.event_pump.pump_events();
-            match self.event_pump.poll_event() {
-                Some(Event::Quit{..}) => { return },
-                Some(Event::KeyDown{..}) => {
-                    let keyboard_state = self.event_pump.keyboard_state();
-                    let pressed_keys: HashSet<Scancode> = keyboard_state.pressed_scancodes().collect();
-
-                    // restart
-                    if pressed_keys.contains(&Scancode::LShift) && pressed_keys.contains(&Scancode::R) ||
-                       pressed_keys.contains(&Scancode::RShift) && pressed_keys.contains(&Scancode::R) {
-                        self.cpu.registers.pc = 0;
-                        self.video_system.clear_screen();
-                    }
-                },
-                _ => {}
-            };
-            let time_start = SystemTime::now();
-            self.cycle();
-            self.timer_routine();
-            self.video_system.render_screen();
-            let elapsed = SystemTime::now().duration_since(time_start).unwrap();
-            if elapsed < spf_nano {
-                thread::sleep(spf_nano - elapsed);
-            }
+            LdILong => {
+                let pc = self.cpu.registers.pc;
+                self.access(address, Access::Fetch, pc as usize, INSTRUCTION_WIDTH as usize)?;
+                let pc = pc as usize;
+                self.cpu.registers.i = ((self.memory[pc] as u16) << 8) | self.memory[pc + 1] as u16;
+                self.advance_pc();
+            },
+            Plane(n) => self.framebuffer.select_planes(n),
+            Audio => {
+                let ireg = self.cpu.registers.i as usize;
+                self.access(address, Access::Read, ireg, AUDIO_PATTERN_LENGTH)?;
+                let mut pattern = [0; AUDIO_PATTERN_LENGTH];
+                pattern.copy_from_slice(&self.memory[ireg..ireg + AUDIO_PATTERN_LENGTH]);
+                self.audio_pattern = Some(pattern);
+            },
+            Pitch(x) => self.pitch = self.v(x),
         }
+        Ok(())
     }
 
     /// Checks and updates the delay and sound timers when necessary.
-    fn timer_routine(&mut self) {
-        let sound_timer = self.sound_timer;
-        if sound_timer > 0 {
+    pub fn tick_timers(&mut self) {
+        if self.sound_timer > 0 {
             self.sound_timer -= 1;
-            self.sound_system.resume();
-        } else {
-            self.sound_system.pause();
         }
-
-        let delay_timer = self.delay_timer;
-        if delay_timer > 0 {
+        if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
     }
 
     /// Fetches the next instruction to be executed by the interpreter
-    fn fetch(&self) -> u16 {
+    fn fetch(&mut self) -> Result<u16, Chip8Error> {
+        let pc = self.cpu.registers.pc;
+        self.access(pc, Access::Fetch, pc as usize, INSTRUCTION_WIDTH as usize)?;
+        let pc = pc as usize;
+        Ok(((self.memory[pc] as u16) << 8) | self.memory[pc + 1] as u16)
+    }
+
+    /// Returns the value of register Vx
+    fn v(&self, x: u8) -> u8 {
+        self.cpu.registers.get(x).unwrap()
+    }
+
+    fn set_v(&mut self, x: u8, value: u8) {
+        self.cpu.registers.set(x, value);
+    }
+
+    /// 8xy1/8xy2/8xy3: sets Vx to `op(Vx, Vy)`
+    fn logic<F: Fn(u8, u8) -> u8>(&mut self, x: u8, y: u8, op: F) {
+        let result = op(self.v(x), self.v(y));
+        self.set_v(x, result);
+        if self.quirks.logic_resets_vf {
+            self.cpu.registers.vf = 0;
+        }
+    }
+
+    /// Skips the next instruction if `condition` holds
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.skip_instruction();
+        }
+    }
+
+    /// Moves the program counter to the next instruction
+    fn advance_pc(&mut self) {
+        self.cpu.registers.pc = self.cpu.registers.pc.wrapping_add(INSTRUCTION_WIDTH as u16);
+    }
+
+    /// Skips the instruction the program counter points at, which takes
+    /// twice the space if it is the XO-CHIP long load (F000 nnnn)
+    fn skip_instruction(&mut self) {
         let pc = self.cpu.registers.pc as usize;
-        ((self.memory[pc] as u16) << 8) | self.memory[(pc + 1) as usize] as u16
+        let long = self.memory.get(pc) == Some(&0xf0) && self.memory.get(pc + 1) == Some(&0x00);
+        self.advance_pc();
+        if long {
+            self.advance_pc();
+        }
+    }
+
+    /// Makes sure the `len` bytes starting at `start` are inside the memory
+    /// and pauses if a watchpoint covers one of them
+    fn access(&mut self, pc: u16, access: Access, start: usize, len: usize) -> Result<(), Chip8Error> {
+        self.check_memory(pc, start, len)?;
+        if self.pause.is_none() {
+            let watchpoints = &self.watchpoints;
+            let hit = (start..start + len)
+                .find(|address| watchpoints.iter().any(|watchpoint| watchpoint.matches(access, *address)));
+            if let Some(address) = hit {
+                self.pause = Some(Pause::Watchpoint { pc, address: address as u16, access });
+            }
+        }
+        Ok(())
+    }
+
+    /// The addresses of the `len` bytes from I that Fx55/Fx65 and
+    /// 5xy2/5xy3 copy registers to or from. In the XO-CHIP 64 KiB they wrap
+    /// around to the start of memory as in Octo, the 4 KiB memory has to
+    /// hold all of them.
+    fn copy_addresses(&mut self, pc: u16, access: Access, len: usize) -> Result<Vec<usize>, Chip8Error> {
+        let start = self.cpu.registers.i as usize;
+        if !self.quirks.large_memory {
+            self.access(pc, access, start, len)?;
+            return Ok((start..start + len).collect());
+        }
+        let size = self.memory.len();
+        let addresses: Vec<usize> = (start..start + len).map(|address| address % size).collect();
+        for &address in &addresses {
+            self.access(pc, access, address, 1)?;
+        }
+        Ok(addresses)
+    }
+
+    /// V0 through VF
+    fn v_registers(&self) -> [u8; 16] {
+        let mut registers = [0; 16];
+        for (x, register) in registers.iter_mut().enumerate() {
+            *register = self.v(x as u8);
+        }
+        registers
+    }
+
+    /// Hands the tracer the entry of the instruction at `address`, which just
+    /// ran
+    fn trace(&mut self, address: u16, opcode: u16, before: [u8; 16]) -> Result<(), Chip8Error> {
+        let next = match opcode {
+            0xf000 => {
+                // missing if the instruction failed at the end of memory
+                let at = address as usize + INSTRUCTION_WIDTH as usize;
+                match (self.memory.get(at), self.memory.get(at + 1)) {
+                    (Some(&high), Some(&low)) => ((high as u16) << 8) | low as u16,
+                    _ => 0,
+                }
+            },
+            _ => 0,
+        };
+        let entry = TraceEntry {
+            pc: address,
+            opcode,
+            next,
+            before,
+            after: self.v_registers(),
+            i: self.cpu.registers.i,
+            sp: self.cpu.registers.sp,
+            dt: self.delay_timer,
+            st: self.sound_timer,
+        };
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.record(&entry)?;
+        }
+        Ok(())
     }
+
+    /// Pauses if a condition turned true
+    fn check_conditions(&mut self) {
+        for index in 0..self.conditions.len() {
+            let (condition, held) = self.conditions[index];
+            let holds = condition.holds(self);
+            self.conditions[index].1 = holds;
+            if holds && !held && self.pause.is_none() {
+                self.pause = Some(Pause::Condition(condition));
+            }
+        }
+    }
+
+    /// Makes sure the `len` bytes starting at `start` are inside the memory
+    fn check_memory(&self, pc: u16, start: usize, len: usize) -> Result<(), Chip8Error> {
+        if start + len > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds { pc, address: start + len - 1 });
+        }
+        Ok(())
+    }
+}
+
+/// Registers x through y, in that order, even if x > y
+fn register_range(x: u8, y: u8) -> Vec<u8> {
+    if x <= y { (x..=y).collect() } else { (y..=x).rev().collect() }
 }
 
 /// Returns the binary decimal coding for the specified number
@@ -860,15 +1252,3 @@ fn u8_to_bcd(input: u8) -> [u8; 3] {
 
     result
 }
-
-/// Takes a result object and returns the inner item or prints the error item
-/// and exits the process
-fn item_or_exit<T, E: ::std::fmt::Display>(res: Result<T, E>) -> T {
-    match res {
-        Ok(i) => {i}
-        Err(e) => {
-            println!("{}", e);
-            process::exit(1);
-        }
-    }
-}
@@ -0,0 +1,202 @@
+//! Which host keys press which keys of the hex keypad.
+//!
+//! Host keys are given by name, the names SDL uses for the keys of a US
+//! keyboard layout ("1", "Q", "Keypad 4", "Left", ...), so the mapping follows
+//! the position of the keys rather than what is printed on them. Game
+//! controller buttons are host keys too, named as in `PadButton`.
+//!
+//! A keymap config file changes the keymap for every program or for one.
+//! Lines before the first section apply to every program; a `[NAME]` section
+//! only to the program file called NAME. In either, `keymap = PRESET` picks a
+//! preset and `X = KEY, KEY...` replaces the host keys of hex key X:
+//!
+//! ```text
+//! # W, the up arrow and the A button for every program
+//! 5 = W, Up, Pad A
+//! [Breakout_Brix_hack_David_Winter_1997.ch8]
+//! keymap = hex
+//! 4 = 4, Left, Pad Left
+//! 6 = 6, Right, Pad Right
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use error::Chip8Error;
+use interpreter::KEY_COUNT;
+
+/// Names accepted by `Keymap::preset`
+pub static KEYMAP_NAMES: [&str; 2] = ["cosmac", "hex"];
+
+/// The COSMAC VIP keypad on the left of a keyboard, row by row
+static COSMAC_LAYOUT: [(u8, &str); 16] = [
+    (0x1, "1"), (0x2, "2"), (0x3, "3"), (0xc, "4"),
+    (0x4, "Q"), (0x5, "W"), (0x6, "E"), (0xd, "R"),
+    (0x7, "A"), (0x8, "S"), (0x9, "D"), (0xe, "F"),
+    (0xa, "Z"), (0x0, "X"), (0xb, "C"), (0xf, "V"),
+];
+
+/// Controller buttons of every preset: the D-pad on 2, 8, 4 and 6, the
+/// directions of most programs, and A on 5, their usual action key
+static PAD_LAYOUT: [(u8, &str); 5] = [
+    (0x2, "Pad Up"), (0x8, "Pad Down"), (0x4, "Pad Left"), (0x6, "Pad Right"), (0x5, "Pad A"),
+];
+
+/// Host keys for each key of the hex keypad
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    keys: [Vec<String>; KEY_COUNT as usize],
+}
+
+impl Default for Keymap {
+    /// The COSMAC layout, the one most programs are written for
+    fn default() -> Self {
+        Keymap::cosmac()
+    }
+}
+
+impl Keymap {
+    /// The COSMAC VIP keypad on 1234/QWER/ASDF/ZXCV:
+    ///
+    /// ```text
+    /// 1 2 3 C      1 2 3 4
+    /// 4 5 6 D  ->  Q W E R
+    /// 7 8 9 E      A S D F
+    /// A 0 B F      Z X C V
+    /// ```
+    pub fn cosmac() -> Self {
+        let mut keymap = Keymap::empty();
+        for &(key, host) in COSMAC_LAYOUT.iter() {
+            keymap.keys[key as usize].push(host.to_string());
+        }
+        keymap.add_pad_layout();
+        keymap
+    }
+
+    /// Each hex digit on the key printed with it, digits on the number row
+    /// and the numeric keypad
+    pub fn hex() -> Self {
+        let mut keymap = Keymap::empty();
+        for (key, host_keys) in keymap.keys.iter_mut().enumerate() {
+            host_keys.push(format!("{:X}", key));
+            if key < 10 {
+                host_keys.push(format!("Keypad {}", key));
+            }
+        }
+        keymap.add_pad_layout();
+        keymap
+    }
+
+    /// Returns the preset with the given name (see `KEYMAP_NAMES`)
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "cosmac" => Some(Keymap::cosmac()),
+            "hex" => Some(Keymap::hex()),
+            _ => None,
+        }
+    }
+
+    /// The names of the host keys that press the hex key
+    pub fn host_keys(&self, key: u8) -> &[String] {
+        self.keys.get(key as usize).map_or(&[], |host_keys| &host_keys[..])
+    }
+
+    /// Replaces the host keys of the hex key, an empty list unmaps it
+    pub fn set_host_keys(&mut self, key: u8, host_keys: Vec<String>) {
+        if key < KEY_COUNT {
+            self.keys[key as usize] = host_keys;
+        }
+    }
+
+    /// The hex keys pressed by the named host key, ignoring case
+    pub fn hex_keys(&self, host_key: &str) -> Vec<u8> {
+        (0..KEY_COUNT)
+            .filter(|key| self.keys[*key as usize].iter().any(|name| name.eq_ignore_ascii_case(host_key)))
+            .collect()
+    }
+
+    fn empty() -> Self {
+        Keymap { keys: Default::default() }
+    }
+
+    fn add_pad_layout(&mut self) {
+        for &(key, button) in PAD_LAYOUT.iter() {
+            self.keys[key as usize].push(button.to_string());
+        }
+    }
+}
+
+/// The changes a keymap config file makes for one program
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeymapConfig {
+    preset: Option<String>,
+    keys: Vec<(u8, Vec<String>)>,
+}
+
+impl KeymapConfig {
+    /// Reads the lines of the config file at `path` that apply to the program
+    /// file called `program`
+    pub fn load(path: &Path, program: &str) -> Result<Self, Chip8Error> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        KeymapConfig::parse(&text, program)
+    }
+
+    /// Parses a config file, keeping the lines that apply to `program`. The
+    /// whole file is checked, other programs' sections included.
+    pub fn parse(text: &str, program: &str) -> Result<Self, Chip8Error> {
+        let mut config = KeymapConfig::default();
+        let mut applies = true;
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| Chip8Error::InvalidKeymap(format!("line {}: {}", number + 1, message));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                let name = line.strip_prefix('[').and_then(|line| line.strip_suffix(']'))
+                    .ok_or_else(|| error("a section is a program file name in brackets".to_string()))?;
+                applies = name.trim() == program;
+                continue;
+            }
+            let (name, value) = match line.find('=') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => return Err(error(format!("expected NAME = VALUE, found '{}'", line))),
+            };
+            if name == "keymap" {
+                if Keymap::preset(value).is_none() {
+                    return Err(error(format!("unknown keymap '{}', expected one of {}",
+                                             value, KEYMAP_NAMES.join(", "))));
+                }
+                if applies {
+                    config.preset = Some(value.to_string());
+                }
+                continue;
+            }
+            let key = match u8::from_str_radix(name, 16) {
+                Ok(key) if name.len() == 1 => key,
+                _ => return Err(error(format!("'{}' is not a hex key, expected 0 - F or keymap", name))),
+            };
+            let host_keys: Vec<String> = value.split(',')
+                .map(|host_key| host_key.trim().to_string())
+                .filter(|host_key| !host_key.is_empty())
+                .collect();
+            if applies {
+                config.keys.push((key, host_keys));
+            }
+        }
+        Ok(config)
+    }
+
+    /// The preset picked by the file, if it picks one
+    pub fn preset(&self) -> Option<&str> {
+        self.preset.as_deref()
+    }
+
+    /// Replaces the host keys the file changes, in the order of the file
+    pub fn apply(&self, keymap: &mut Keymap) {
+        for (key, host_keys) in &self.keys {
+            keymap.set_host_keys(*key, host_keys.clone());
+        }
+    }
+}
@@ -1,3 +1,44 @@
+mod asm;
 mod cpu;
+mod debugger;
+mod disasm;
+mod error;
+mod framebuffer;
+mod gamepad;
+mod gdb;
+mod instruction;
 mod interpreter;
-pub use interpreter::Interpreter;
+mod keymap;
+mod movie;
+mod quirks;
+mod rewind;
+mod rng;
+mod runner;
+mod screenshot;
+mod state;
+mod stats;
+mod trace;
+mod watch;
+pub mod backend;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+pub use asm::{assemble, assemble_file};
+pub use debugger::Debugger;
+pub use disasm::{disassemble, mnemonic};
+pub use error::Chip8Error;
+pub use gamepad::{Gamepads, PadButton, PadEvent, STICK_DEAD_ZONE};
+pub use gdb::GdbServer;
+pub use framebuffer::{Collision, Framebuffer, SCREEN_WIDTH, SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT, PLANE_COUNT, PALETTE};
+pub use instruction::{Instruction, UnknownOpcode};
+pub use interpreter::{Interpreter, Pause, StackPolicy, KEY_COUNT, STACK_DEPTH, AUDIO_PATTERN_LENGTH, DEFAULT_PITCH};
+pub use keymap::{Keymap, KeymapConfig, KEYMAP_NAMES};
+pub use movie::{Movie, MovieFrame, MOVIE_MAGIC, MOVIE_VERSION};
+pub use quirks::{Quirks, PRESET_NAMES, QUIRK_COUNT};
+pub use rewind::{RewindBuffer, DEFAULT_REWIND_SECONDS};
+pub use rng::{RandomSource, SeededRandom, ScriptedRandom, RecordingRandom};
+pub use runner::{Runner, FRAME_RATE, DEFAULT_CPU_HZ};
+pub use screenshot::{save_screenshot, write_png, write_pbm};
+pub use state::{STATE_MAGIC, STATE_VERSION};
+pub use stats::FrameStats;
+pub use trace::{Tracer, TraceEntry, TextTrace, BinaryTrace, TraceReader, RingTrace, TraceFilter, FilteredTrace, TRACE_MAGIC, TRACE_VERSION};
+pub use watch::{Access, Watchpoint, Register, Comparison, Condition, parse_address, parse_number};
@@ -2,25 +2,580 @@
 extern crate clap;
 extern crate pschip8;
 
-use std::path::Path;
-use clap::{Arg, App};
-use pschip8::Interpreter;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
+use pschip8::backend::{Display, Audio, Input, NullDisplay, NullAudio, NullInput};
+use pschip8::{Tracer, TextTrace, BinaryTrace, TraceReader, RingTrace, TraceFilter, FilteredTrace};
+use pschip8::{Keymap, KeymapConfig, KEYMAP_NAMES};
+use pschip8::{Runner, Interpreter, Debugger, Movie, GdbServer, Chip8Error, Quirks, PRESET_NAMES, DEFAULT_CPU_HZ, DEFAULT_REWIND_SECONDS, FRAME_RATE, parse_address};
+
+/// Seconds of the program `continue` runs without a window, where nothing
+/// can interrupt it
+const HEADLESS_CONTINUE_SECONDS: u32 = 10;
+/// Seconds `run --headless` runs when no number of frames is given
+const HEADLESS_RUN_SECONDS: u32 = 60;
 
 fn main() {
+    let default_cpu_hz = DEFAULT_CPU_HZ.to_string();
+    let frames_help = format!("Stops after N frames (60 per second) [default: {} without a window]",
+                              HEADLESS_RUN_SECONDS * FRAME_RATE);
+    let default_rewind = DEFAULT_REWIND_SECONDS.to_string();
     let matches = App::new("pschip8")
         .version(crate_version!())
         .author("Francis A. <francisagyapong2@gmail.com>")
         .about("Pretty Simple Chip8 Interpreter")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("program")
              .short("p")
              .long("program")
              .value_name("FILE")
              .help("The chip-8 program file")
              .required(true))
+        .arg(Arg::with_name("quirks")
+             .short("q")
+             .long("quirks")
+             .value_name("PROFILE")
+             .help("The platform whose behavior to emulate")
+             .possible_values(&PRESET_NAMES)
+             .default_value("default"))
+        .args(&keymap_args())
+        .arg(Arg::with_name("rpl")
+             .long("rpl-file")
+             .value_name("FILE")
+             .help("Where to keep the SUPER-CHIP RPL user flags [default: the program file with an .rpl extension]"))
+        .arg(Arg::with_name("seed")
+             .long("seed")
+             .value_name("SEED")
+             .help("Seed for the random numbers, runs with the same seed and input are identical"))
+        .arg(Arg::with_name("rewind")
+             .long("rewind-seconds")
+             .value_name("SECONDS")
+             .help("How much play Backspace can rewind, 0 turns rewinding off")
+             .default_value(&default_rewind))
+        .arg(Arg::with_name("cpu-hz")
+             .long("cpu-hz")
+             .value_name("HZ")
+             .help("Instructions executed per second, the timers always run at 60 Hz")
+             .default_value(&default_cpu_hz))
+        .arg(Arg::with_name("trace")
+             .long("trace")
+             .value_name("FILE")
+             .help("Writes every executed instruction to the file"))
+        .arg(Arg::with_name("trace-format")
+             .long("trace-format")
+             .value_name("FORMAT")
+             .help("Lines of text or the compact binary form that the trace subcommand prints")
+             .possible_values(&["text", "binary"])
+             .default_value("text"))
+        .arg(Arg::with_name("trace-range")
+             .long("trace-range")
+             .value_name("START-END")
+             .help("Traces only the instructions at these addresses, e.g. 0x200-0x2ff"))
+        .arg(Arg::with_name("trace-ops")
+             .long("trace-ops")
+             .value_name("CLASSES")
+             .help("Traces only the opcodes starting with these hex digits, e.g. 8,d"))
+        .arg(Arg::with_name("trace-ring")
+             .long("trace-ring")
+             .value_name("N")
+             .help("Keeps only the last N instructions and writes them out if the program crashes \
+                    [default output: standard error]"))
+        .arg(Arg::with_name("record-movie")
+             .long("record-movie")
+             .value_name("FILE")
+             .help("Records the keys of every frame to a movie that --play-movie replays exactly")
+             .conflicts_with("play-movie"))
+        .args(&play_movie_args())
+        .arg(Arg::with_name("stats")
+             .long("stats")
+             .help("Prints how long running and drawing the frames took on exit"))
+        .subcommand(SubCommand::with_name("disasm")
+                    .about("Prints the instructions of a chip-8 program")
+                    .arg(Arg::with_name("rom")
+                         .value_name("ROM")
+                         .help("The chip-8 program file")
+                         .required(true))
+                    .arg(Arg::with_name("origin")
+                         .long("origin")
+                         .value_name("ADDRESS")
+                         .help("The address the program is loaded at")
+                         .default_value("0x200")))
+        .subcommand(SubCommand::with_name("asm")
+                    .about("Assembles a chip-8 program")
+                    .arg(Arg::with_name("source")
+                         .value_name("SOURCE")
+                         .help("The assembly source file")
+                         .required(true))
+                    .arg(Arg::with_name("output")
+                         .short("o")
+                         .long("output")
+                         .value_name("FILE")
+                         .help("Where to write the program [default: the source file with a .ch8 extension]")))
+        .subcommand(SubCommand::with_name("run")
+                    .about("Runs a chip-8 program, optionally without a window, and saves a screenshot")
+                    .args(&rom_args(&default_cpu_hz))
+                    .args(&play_movie_args())
+                    .arg(Arg::with_name("frames")
+                         .long("frames")
+                         .value_name("N")
+                         .help(&frames_help)
+                         .requires("headless"))
+                    .arg(Arg::with_name("until-pc")
+                         .long("until-pc")
+                         .value_name("ADDRESS")
+                         .help("Stops before the instruction at ADDRESS runs, fails if it isn't reached within the frames")
+                         .requires("headless"))
+                    .arg(Arg::with_name("screenshot")
+                         .long("screenshot")
+                         .value_name("FILE")
+                         .help("Saves the screen as a .png or .pbm image when the program stops"))
+                    .arg(Arg::with_name("scale")
+                         .long("scale")
+                         .value_name("N")
+                         .help("Draws each pixel of the screenshot as an N x N square")
+                         .default_value("1"))
+                    .arg(Arg::with_name("stats")
+                         .long("stats")
+                         .help("Prints how long running and drawing the frames took on exit")))
+        .subcommand(SubCommand::with_name("trace")
+                    .about("Prints a binary trace as text")
+                    .arg(Arg::with_name("file")
+                         .value_name("FILE")
+                         .help("The trace written with --trace-format binary")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("debug")
+                    .about("Runs a chip-8 program under an interactive debugger")
+                    .args(&rom_args(&default_cpu_hz)))
+        .subcommand(SubCommand::with_name("gdb")
+                    .about("Runs a chip-8 program under a GDB remote protocol stub")
+                    .args(&rom_args(&default_cpu_hz))
+                    .arg(Arg::with_name("port")
+                         .long("port")
+                         .value_name("PORT")
+                         .help("The local TCP port the debugger connects to")
+                         .default_value("9000")))
         .get_matches();
 
+    let result = match matches.subcommand() {
+        ("disasm", Some(sub)) => disasm(sub),
+        ("asm", Some(sub)) => asm(sub),
+        ("run", Some(sub)) => run_rom(sub),
+        ("trace", Some(sub)) => print_trace(sub),
+        ("debug", Some(sub)) => debug(sub),
+        ("gdb", Some(sub)) => gdb(sub),
+        _ => run_program(&matches),
+    };
+    if let Err(e) = result {
+        eprintln!("pschip8: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Options of the subcommands that run a program file
+fn rom_args<'a, 'b>(default_cpu_hz: &'a str) -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("rom")
+            .value_name("ROM")
+            .help("The chip-8 program file")
+            .required(true),
+        Arg::with_name("headless")
+            .long("headless")
+            .help("Runs without a window, sound or keyboard input"),
+        Arg::with_name("quirks")
+            .short("q")
+            .long("quirks")
+            .value_name("PROFILE")
+            .help("The platform whose behavior to emulate")
+            .possible_values(&PRESET_NAMES)
+            .default_value("default"),
+        Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .help("Seed for the random numbers"),
+        Arg::with_name("cpu-hz")
+            .long("cpu-hz")
+            .value_name("HZ")
+            .help("Instructions executed per second while the program runs")
+            .default_value(default_cpu_hz),
+    ].into_iter().chain(keymap_args()).collect()
+}
+
+/// Options that pick the keymap of the window
+fn keymap_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("keymap")
+            .long("keymap")
+            .value_name("KEYMAP")
+            .help("Which host keys press the hex keys, 1234/QWER/ASDF/ZXCV (cosmac) or the printed digits (hex) \
+                   [default: the one the keymap file picks, else cosmac]")
+            .possible_values(&KEYMAP_NAMES),
+        Arg::with_name("keymap-file")
+            .long("keymap-file")
+            .value_name("FILE")
+            .help("Keymap changes for every program or for some [default: ~/.config/pschip8/keymap.conf]"),
+    ]
+}
+
+/// Options of the commands that can play an input movie
+fn play_movie_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("play-movie")
+            .long("play-movie")
+            .value_name("FILE")
+            .help("Replays a movie with the quirks, seed and clock it was recorded with"),
+        Arg::with_name("stop-on-desync")
+            .long("stop-on-desync")
+            .help("Stops the movie with an error as soon as the run differs from the recording")
+            .requires("play-movie"),
+    ]
+}
+
+/// Frontend settings from the command line
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+struct Options {
+    cpu_hz: u32,
+    rewind_seconds: u32,
+    keymap: Keymap,
+    record_movie: Option<PathBuf>,
+    play_movie: Option<Movie>,
+    stop_on_desync: bool,
+    print_stats: bool,
+}
+
+impl Options {
+    /// Settings with no movie and no stats
+    fn new(cpu_hz: u32, rewind_seconds: u32) -> Self {
+        Options {
+            cpu_hz,
+            rewind_seconds,
+            keymap: Keymap::default(),
+            record_movie: None,
+            play_movie: None,
+            stop_on_desync: false,
+            print_stats: false,
+        }
+    }
+
+    /// Sets up the keymap for the program at `program_path` from the
+    /// command line and the keymap file
+    fn keymap_args(&mut self, matches: &ArgMatches, program_path: &Path) -> Result<(), Chip8Error> {
+        let default_path = env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".config/pschip8/keymap.conf"))
+            .filter(|path| path.exists());
+        let config = match matches.value_of("keymap-file").map(PathBuf::from).or(default_path) {
+            Some(path) => {
+                let program = program_path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+                KeymapConfig::load(&path, &program).map_err(|e| match e {
+                    Chip8Error::InvalidKeymap(reason) => {
+                        Chip8Error::InvalidKeymap(format!("{}: {}", path.display(), reason))
+                    },
+                    e => e,
+                })?
+            },
+            None => KeymapConfig::default(),
+        };
+        let preset = matches.value_of("keymap").or(config.preset()).unwrap_or("cosmac");
+        self.keymap = Keymap::preset(preset).unwrap();
+        config.apply(&mut self.keymap);
+        Ok(())
+    }
+
+    /// Reads the movie options from the command line
+    fn movie_args(&mut self, matches: &ArgMatches) -> Result<(), Chip8Error> {
+        self.record_movie = matches.value_of("record-movie").map(PathBuf::from);
+        if let Some(path) = matches.value_of("play-movie") {
+            self.play_movie = Some(Movie::load(Path::new(path))?);
+        }
+        self.stop_on_desync = matches.is_present("stop-on-desync");
+        Ok(())
+    }
+}
+
+fn run_program(matches: &ArgMatches) -> Result<(), Chip8Error> {
     let program_path = Path::new(matches.value_of("program").unwrap());
+    let rpl_path = matches.value_of("rpl").map(PathBuf::from)
+        .unwrap_or_else(|| program_path.with_extension("rpl"));
+    let mut intp = Interpreter::new();
+    intp.set_quirks(Quirks::preset(matches.value_of("quirks").unwrap()).unwrap())?;
+    if matches.is_present("seed") {
+        intp.set_seed(value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit()));
+    }
+    intp.load_program_from_file(program_path)?;
+    let mut options = Options::new(value_t!(matches, "cpu-hz", u32).unwrap_or_else(|e| e.exit()),
+                                   value_t!(matches, "rewind", u32).unwrap_or_else(|e| e.exit()));
+    options.movie_args(matches)?;
+    options.keymap_args(matches, program_path)?;
+    options.print_stats = matches.is_present("stats");
+    // a replay starts from the flags in the movie and must not change the saved ones
+    if options.play_movie.is_none() {
+        intp.set_rpl_path(&rpl_path)?;
+    }
+    let ring = set_tracer(&mut intp, matches)?;
+    let result = run(&mut intp, program_path, &options);
+    if let (Err(_), Some(ring)) = (&result, ring) {
+        match matches.value_of("trace") {
+            Some(path) => ring.dump(BufWriter::new(File::create(path)?))?,
+            None => ring.dump(io::stderr())?,
+        }
+    }
+    result
+}
+
+/// Sets up the tracing asked for on the command line. Returns the ring
+/// buffer to dump on a crash if there is one.
+fn set_tracer(intp: &mut Interpreter, matches: &ArgMatches) -> Result<Option<RingTrace>, Chip8Error> {
+    let mut filter = TraceFilter::default();
+    if let Some(range) = matches.value_of("trace-range") {
+        let bounds = range.find('-').and_then(|at| {
+            Some((parse_address(&range[..at]).ok()?, parse_address(&range[at + 1..]).ok()?))
+        });
+        let (start, end) = bounds.unwrap_or_else(|| {
+            clap::Error::value_validation_auto(format!("invalid address range '{}'", range)).exit()
+        });
+        filter.start = start;
+        filter.end = end;
+    }
+    if let Some(ops) = matches.value_of("trace-ops") {
+        filter.classes = ops.split(',').try_fold(0, |classes, class| {
+            u8::from_str_radix(class.trim(), 16).ok().filter(|class| *class <= 0xf).map(|class| classes | 1 << class)
+        }).unwrap_or_else(|| {
+            clap::Error::value_validation_auto(format!("invalid opcode classes '{}'", ops)).exit()
+        });
+    }
+
+    if matches.is_present("trace-ring") {
+        let ring = RingTrace::new(value_t!(matches, "trace-ring", usize).unwrap_or_else(|e| e.exit()));
+        intp.set_tracer(Some(Box::new(FilteredTrace::new(filter, ring.clone()))));
+        return Ok(Some(ring));
+    }
+    if let Some(path) = matches.value_of("trace") {
+        let file = BufWriter::new(File::create(path)?);
+        let tracer: Box<dyn Tracer> = match matches.value_of("trace-format") {
+            Some("binary") => Box::new(FilteredTrace::new(filter, BinaryTrace::new(file)?)),
+            _ => Box::new(FilteredTrace::new(filter, TextTrace::new(file))),
+        };
+        intp.set_tracer(Some(tracer));
+    }
+    Ok(None)
+}
+
+fn print_trace(matches: &ArgMatches) -> Result<(), Chip8Error> {
+    let file = BufReader::new(File::open(matches.value_of("file").unwrap())?);
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    for entry in TraceReader::new(file)? {
+        writeln!(output, "{}", entry?)?;
+    }
+    Ok(())
+}
+
+fn disasm(matches: &ArgMatches) -> Result<(), Chip8Error> {
+    let origin = value_t!(matches, "origin", String).unwrap_or_else(|e| e.exit());
+    let origin = parse_address(&origin).unwrap_or_else(|_| {
+        clap::Error::value_validation_auto(format!("invalid address '{}'", origin)).exit()
+    });
+    let mut bytes = Vec::new();
+    File::open(matches.value_of("rom").unwrap())?.read_to_end(&mut bytes)?;
+    print!("{}", pschip8::disassemble(&bytes, origin));
+    Ok(())
+}
+
+fn asm(matches: &ArgMatches) -> Result<(), Chip8Error> {
+    let source_path = Path::new(matches.value_of("source").unwrap());
+    let output_path = matches.value_of("output").map(PathBuf::from)
+        .unwrap_or_else(|| source_path.with_extension("ch8"));
+    let program = pschip8::assemble_file(source_path)?;
+    File::create(output_path)?.write_all(&program)?;
+    Ok(())
+}
+
+/// Loads the program to debug, returns it with the CPU clock to run it at
+fn load_rom(matches: &ArgMatches) -> Result<(Interpreter, u32), Chip8Error> {
     let mut intp = Interpreter::new();
-    intp.load_program_from_file(&program_path);
-    intp.run();
+    intp.set_quirks(Quirks::preset(matches.value_of("quirks").unwrap()).unwrap())?;
+    if matches.is_present("seed") {
+        intp.set_seed(value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit()));
+    }
+    intp.load_program_from_file(Path::new(matches.value_of("rom").unwrap()))?;
+    let cpu_hz = value_t!(matches, "cpu-hz", u32).unwrap_or_else(|e| e.exit());
+    Ok((intp, cpu_hz))
+}
+
+fn run_rom(matches: &ArgMatches) -> Result<(), Chip8Error> {
+    let (mut intp, cpu_hz) = load_rom(matches)?;
+    let mut options = Options::new(cpu_hz, 0);
+    options.movie_args(matches)?;
+    options.print_stats = matches.is_present("stats");
+    let until_pc = matches.value_of("until-pc").map(|text| {
+        parse_address(text).unwrap_or_else(|_| {
+            clap::Error::value_validation_auto(format!("invalid address '{}'", text)).exit()
+        })
+    });
+    if matches.is_present("headless") {
+        let frames = match matches.value_of("frames") {
+            Some(_) => value_t!(matches, "frames", usize).unwrap_or_else(|e| e.exit()),
+            None => (HEADLESS_RUN_SECONDS * FRAME_RATE) as usize,
+        };
+        if let Some(pc) = until_pc {
+            intp.add_breakpoint(pc);
+        }
+        let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+        runner.set_cpu_hz(cpu_hz);
+        start_movie(&mut runner, &mut intp, &options)?;
+        let result = runner.run_frames(&mut intp, frames);
+        print_errors(&mut runner);
+        finish_movie(&mut runner, &options)?;
+        print_stats(&runner, &options);
+        result?;
+    } else {
+        let program_path = Path::new(matches.value_of("rom").unwrap());
+        options.keymap_args(matches, program_path)?;
+        run(&mut intp, program_path, &options)?;
+    }
+
+    if let Some(path) = matches.value_of("screenshot") {
+        let scale = value_t!(matches, "scale", u32).unwrap_or_else(|e| e.exit());
+        pschip8::save_screenshot(intp.framebuffer(), Path::new(path), scale)?;
+    }
+    if let Some(pc) = until_pc {
+        if intp.pause().is_none() {
+            return Err(Chip8Error::NotReached { address: pc });
+        }
+    }
+    Ok(())
+}
+
+/// Starts playing or recording the movie given on the command line
+fn start_movie<D, A, I>(runner: &mut Runner<D, A, I>, intp: &mut Interpreter,
+                        options: &Options) -> Result<(), Chip8Error>
+    where D: Display, A: Audio, I: Input
+{
+    if let Some(ref movie) = options.play_movie {
+        runner.play_movie(intp, movie.clone(), options.stop_on_desync)?;
+    } else if options.record_movie.is_some() {
+        runner.record_movie(intp)?;
+    }
+    Ok(())
+}
+
+/// Saves the movie recorded and warns if the movie played desynced
+fn finish_movie<D, A, I>(runner: &mut Runner<D, A, I>, options: &Options) -> Result<(), Chip8Error>
+    where D: Display, A: Audio, I: Input
+{
+    if let (Some(frame), false) = (runner.movie_desync(), options.stop_on_desync) {
+        eprintln!("pschip8: the run desynced from the movie at frame {}", frame);
+    }
+    if let (Some(movie), Some(path)) = (runner.take_movie(), options.record_movie.as_ref()) {
+        movie.save(path)?;
+    }
+    Ok(())
+}
+
+/// Prints the failures the run carried on after
+fn print_errors<D, A, I>(runner: &mut Runner<D, A, I>)
+    where D: Display, A: Audio, I: Input
+{
+    for error in runner.take_errors() {
+        eprintln!("pschip8: {}", error);
+    }
+}
+
+/// Prints the frame times if the command line asked for them
+fn print_stats<D, A, I>(runner: &Runner<D, A, I>, options: &Options)
+    where D: Display, A: Audio, I: Input
+{
+    if options.print_stats {
+        eprintln!("pschip8: {}", runner.stats());
+    }
+}
+
+fn debug(matches: &ArgMatches) -> Result<(), Chip8Error> {
+    let (mut intp, cpu_hz) = load_rom(matches)?;
+    let mut debugger = Debugger::new();
+    if matches.is_present("headless") {
+        let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+        runner.set_cpu_hz(cpu_hz);
+        debugger.set_frame_limit(Some((HEADLESS_CONTINUE_SECONDS * FRAME_RATE) as usize));
+        debugger.set_headless(true);
+        let stdin = io::stdin();
+        debugger.run(&mut runner, &mut intp, stdin.lock(), io::stdout())
+    } else {
+        let mut options = Options::new(cpu_hz, 0);
+        options.keymap_args(matches, Path::new(matches.value_of("rom").unwrap()))?;
+        debug_windowed(&mut debugger, &mut intp, &options)
+    }
+}
+
+fn gdb(matches: &ArgMatches) -> Result<(), Chip8Error> {
+    let (mut intp, cpu_hz) = load_rom(matches)?;
+    let port = value_t!(matches, "port", u16).unwrap_or_else(|e| e.exit());
+    let server = GdbServer::bind(("127.0.0.1", port))?;
+    eprintln!("pschip8: waiting for a debugger on {}", server.local_addr()?);
+    if matches.is_present("headless") {
+        let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+        runner.set_cpu_hz(cpu_hz);
+        server.serve(&mut runner, &mut intp)
+    } else {
+        let mut options = Options::new(cpu_hz, 0);
+        options.keymap_args(matches, Path::new(matches.value_of("rom").unwrap()))?;
+        gdb_windowed(&server, &mut intp, &options)
+    }
+}
+
+/// Opens the window with the clock and keymap of `options`
+#[cfg(feature = "sdl")]
+fn open_frontend(options: &Options) -> Result<pschip8::sdl::SdlFrontend, Chip8Error> {
+    let mut frontend = pschip8::sdl::frontend()?;
+    frontend.set_cpu_hz(options.cpu_hz);
+    frontend.input_mut().set_keymap(&options.keymap)?;
+    Ok(frontend)
+}
+
+#[cfg(feature = "sdl")]
+fn run(intp: &mut Interpreter, program_path: &Path, options: &Options) -> Result<(), Chip8Error> {
+    let mut frontend = open_frontend(options)?;
+    frontend.set_rewind_seconds(options.rewind_seconds);
+    frontend.set_state_path(program_path.to_path_buf());
+    start_movie(&mut frontend, intp, options)?;
+    let result = frontend.run(intp);
+    print_errors(&mut frontend);
+    finish_movie(&mut frontend, options)?;
+    print_stats(&frontend, options);
+    result
+}
+
+#[cfg(feature = "sdl")]
+fn debug_windowed(debugger: &mut Debugger, intp: &mut Interpreter, options: &Options) -> Result<(), Chip8Error> {
+    let mut frontend = open_frontend(options)?;
+    let stdin = io::stdin();
+    let result = debugger.run(&mut frontend, intp, stdin.lock(), io::stdout());
+    print_errors(&mut frontend);
+    result
+}
+
+#[cfg(not(feature = "sdl"))]
+fn debug_windowed(_debugger: &mut Debugger, _intp: &mut Interpreter, _options: &Options) -> Result<(), Chip8Error> {
+    Err(Chip8Error::BackendInit("pschip8 was built without the sdl feature, use --headless".to_string()))
+}
+
+#[cfg(feature = "sdl")]
+fn gdb_windowed(server: &GdbServer, intp: &mut Interpreter, options: &Options) -> Result<(), Chip8Error> {
+    let mut frontend = open_frontend(options)?;
+    let result = server.serve(&mut frontend, intp);
+    print_errors(&mut frontend);
+    result
+}
+
+#[cfg(not(feature = "sdl"))]
+fn gdb_windowed(_server: &GdbServer, _intp: &mut Interpreter, _options: &Options) -> Result<(), Chip8Error> {
+    Err(Chip8Error::BackendInit("pschip8 was built without the sdl feature, use --headless".to_string()))
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run(_intp: &mut Interpreter, _program_path: &Path, _options: &Options) -> Result<(), Chip8Error> {
+    Err(Chip8Error::BackendInit("pschip8 was built without the sdl feature".to_string()))
 }
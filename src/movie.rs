@@ -0,0 +1,166 @@
+//! Input movies: the keypad state of every frame along with everything else
+//! a run depends on, so that playing a movie back repeats the run exactly.
+//!
+//! A movie starts with a header: the magic bytes, the format version, the
+//! FNV-1a hash of the program, the seed of the random numbers, the CPU clock,
+//! the quirks and the RPL user flags the program started with. The frames
+//! follow, each the keys held as a bit mask (bit n for key n), a flags byte
+//! and the hash of the machine's save state after the frame ran, which is
+//! how playback notices it no longer does what the recording did. Every
+//! multi-byte value is big-endian.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use error::Chip8Error;
+use interpreter::Interpreter;
+use quirks::{Quirks, QUIRK_COUNT};
+use state::{rom_hash, StateReader, StateWriter};
+
+/// Bytes every movie starts with
+pub const MOVIE_MAGIC: &[u8; 8] = b"PSC8MOVI";
+/// Version of the movie format written by this interpreter
+pub const MOVIE_VERSION: u16 = 1;
+
+/// Flag of a frame that starts by restarting the program
+const FRAME_RESET: u8 = 0x01;
+
+/// One frame of a movie
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieFrame {
+    /// Bit n is set if key n is held during the frame
+    pub keys: u16,
+    /// The program was restarted before the frame ran
+    pub reset: bool,
+    /// Hash of the machine after the frame ran, see `checksum`
+    pub checksum: u64,
+}
+
+/// A recorded run of a program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    /// Hash of the program the movie was recorded with
+    pub rom_hash: u64,
+    pub seed: u64,
+    /// Instructions executed per second
+    pub cpu_hz: u32,
+    pub quirks: Quirks,
+    /// The SUPER-CHIP RPL user flags when the recording started
+    pub rpl_flags: Vec<u8>,
+    frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    /// Starts an empty movie of the program loaded in `intp`, with its seed,
+    /// quirks and RPL flags. Fails if the random numbers are not seeded.
+    pub fn new(intp: &Interpreter, cpu_hz: u32) -> Result<Self, Chip8Error> {
+        let seed = intp.seed()
+            .ok_or_else(|| invalid("the random numbers of a movie need a seed"))?;
+        Ok(Movie {
+            rom_hash: rom_hash(intp.program()),
+            seed,
+            cpu_hz,
+            quirks: *intp.quirks(),
+            rpl_flags: intp.rpl_flags().to_vec(),
+            frames: Vec::new(),
+        })
+    }
+
+    /// The frames recorded so far
+    pub fn frames(&self) -> &[MovieFrame] {
+        &self.frames
+    }
+
+    /// Appends a frame
+    pub fn push(&mut self, frame: MovieFrame) {
+        self.frames.push(frame);
+    }
+
+    /// Prepares `intp` to play the movie back: the quirks, seed and RPL
+    /// flags are set and the program restarted. Fails if a different program
+    /// is loaded.
+    pub fn start(&self, intp: &mut Interpreter) -> Result<(), Chip8Error> {
+        if rom_hash(intp.program()) != self.rom_hash {
+            return Err(invalid("the movie was recorded with a different program"));
+        }
+        intp.set_quirks(self.quirks)?;
+        intp.set_seed(self.seed);
+        intp.set_rpl_flags(&self.rpl_flags);
+        intp.reset();
+        Ok(())
+    }
+
+    /// Reads a movie written by `save`
+    pub fn load(path: &Path) -> Result<Self, Chip8Error> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        Movie::from_bytes(&data)
+    }
+
+    /// Writes the movie to the file at `path`
+    pub fn save(&self, path: &Path) -> Result<(), Chip8Error> {
+        File::create(path)?.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Serializes the movie
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut movie = StateWriter::default();
+        movie.bytes(MOVIE_MAGIC);
+        movie.u16(MOVIE_VERSION);
+        movie.u64(self.rom_hash);
+        movie.u64(self.seed);
+        movie.u32(self.cpu_hz);
+        for flag in &self.quirks.to_flags() {
+            movie.bool(*flag);
+        }
+        movie.block(&self.rpl_flags);
+        movie.u32(self.frames.len() as u32);
+        for frame in &self.frames {
+            movie.u16(frame.keys);
+            movie.u8(if frame.reset { FRAME_RESET } else { 0 });
+            movie.u64(frame.checksum);
+        }
+        movie.finish()
+    }
+
+    /// Reads a movie serialized by `to_bytes`
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Chip8Error> {
+        let mut reader = StateReader::without_header(data, invalid);
+        if reader.bytes(MOVIE_MAGIC.len()).ok() != Some(&MOVIE_MAGIC[..]) {
+            return Err(invalid("not a movie"));
+        }
+        let version = reader.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(invalid(&format!("unsupported movie version {}", version)));
+        }
+        let rom_hash = reader.u64()?;
+        let seed = reader.u64()?;
+        let cpu_hz = reader.u32()?;
+        let flags = (0..QUIRK_COUNT).map(|_| reader.bool()).collect::<Result<Vec<_>, _>>()?;
+        let quirks = Quirks::from_flags(&flags);
+        let len = reader.u32()? as usize;
+        let rpl_flags = reader.bytes(len)?.to_vec();
+        let count = reader.u32()? as usize;
+        let mut frames = Vec::with_capacity(::std::cmp::min(count, data.len()));
+        for _ in 0..count {
+            let keys = reader.u16()?;
+            let flags = reader.u8()?;
+            frames.push(MovieFrame { keys, reset: flags & FRAME_RESET != 0, checksum: reader.u64()? });
+        }
+        if !reader.is_empty() {
+            return Err(invalid("unexpected data after the last frame"));
+        }
+        Ok(Movie { rom_hash, seed, cpu_hz, quirks, rpl_flags, frames })
+    }
+}
+
+/// Hash of the complete machine, equal for two interpreters only if they
+/// are in the same state
+pub fn checksum(intp: &Interpreter) -> u64 {
+    rom_hash(&intp.save_state())
+}
+
+fn invalid(reason: &str) -> Chip8Error {
+    Chip8Error::InvalidMovie(reason.to_string())
+}
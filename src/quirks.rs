@@ -0,0 +1,127 @@
+/// Behaviors that differ between chip-8 platforms.
+///
+/// `Quirks::default()` is the behavior this interpreter always had, the named
+/// presets follow the platforms most programs were written for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// 8xy6/8xye shift Vy and store the result in Vx, instead of shifting Vx
+    /// in place
+    pub shift_uses_vy: bool,
+    /// Fx55/Fx65 leave I pointing just past the last byte accessed, instead
+    /// of leaving I unchanged
+    pub load_store_increments_i: bool,
+    /// Bnnn jumps to nnn + Vx, x being the high nibble of nnn, instead of
+    /// nnn + V0
+    pub jump_uses_vx: bool,
+    /// 8xy1/8xy2/8xy3 set VF to 0
+    pub logic_resets_vf: bool,
+    /// Sprites drawn across the edges of the screen wrap around to the other
+    /// side instead of being clipped
+    pub wrap_sprites: bool,
+    /// 64 KiB of memory, as in XO-CHIP, instead of 4 KiB
+    pub large_memory: bool,
+    /// Fx0A completes when the key pressed is released, as on the COSMAC
+    /// VIP, instead of as soon as it is pressed
+    pub key_wait_release: bool,
+    /// In the high resolution mode Dxyn sets VF to the number of sprite rows
+    /// that erased a pixel or were clipped at the bottom, as SUPER-CHIP 1.1
+    /// does, instead of to 1 if any pixel was erased
+    pub collision_counts_rows: bool,
+    /// 00FE/00FF clear the screen, as XO-CHIP does, instead of keeping the
+    /// picture as SUPER-CHIP 1.1 does
+    pub resolution_change_clears: bool,
+}
+
+/// Number of flags in `Quirks::to_flags`
+pub const QUIRK_COUNT: usize = 9;
+
+/// Names accepted by `Quirks::preset`
+pub static PRESET_NAMES: [&str; 5] = ["default", "vip", "chip48", "schip", "xochip"];
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            wrap_sprites: false,
+            large_memory: false,
+            key_wait_release: true,
+            collision_counts_rows: false,
+            resolution_change_clears: false,
+        }
+    }
+
+    /// CHIP-48 on the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            wrap_sprites: false,
+            large_memory: false,
+            key_wait_release: false,
+            collision_counts_rows: false,
+            resolution_change_clears: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1 on the HP-48 calculators, CHIP-48 with its own
+    /// collision counting in the high resolution mode
+    pub fn superchip() -> Self {
+        Quirks { collision_counts_rows: true, ..Quirks::chip48() }
+    }
+
+    /// XO-CHIP, as implemented by Octo
+    pub fn xochip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            wrap_sprites: true,
+            large_memory: true,
+            key_wait_release: false,
+            collision_counts_rows: false,
+            resolution_change_clears: true,
+        }
+    }
+
+    /// The quirks as flags, in the order save states and movies store them
+    pub fn to_flags(&self) -> [bool; QUIRK_COUNT] {
+        [self.shift_uses_vy, self.load_store_increments_i, self.jump_uses_vx, self.logic_resets_vf,
+         self.wrap_sprites, self.large_memory, self.key_wait_release, self.collision_counts_rows,
+         self.resolution_change_clears]
+    }
+
+    /// The quirks stored by `to_flags`, missing flags are off
+    pub fn from_flags(flags: &[bool]) -> Self {
+        let flag = |index: usize| flags.get(index).cloned().unwrap_or(false);
+        Quirks {
+            shift_uses_vy: flag(0),
+            load_store_increments_i: flag(1),
+            jump_uses_vx: flag(2),
+            logic_resets_vf: flag(3),
+            wrap_sprites: flag(4),
+            large_memory: flag(5),
+            key_wait_release: flag(6),
+            collision_counts_rows: flag(7),
+            resolution_change_clears: flag(8),
+        }
+    }
+
+    /// Returns the preset with the given name (see `PRESET_NAMES`)
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Quirks::default()),
+            "vip" => Some(Quirks::cosmac_vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip" => Some(Quirks::superchip()),
+            "xochip" => Some(Quirks::xochip()),
+            _ => None,
+        }
+    }
+}
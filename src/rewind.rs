@@ -0,0 +1,144 @@
+//! Rewinding: a ring buffer of recent save states.
+//!
+//! Consecutive frames differ in a handful of bytes, so only the newest state
+//! is kept whole. Every older state is stored as the XOR of it and the state
+//! after it, run-length encoded, which is mostly runs of zeros.
+
+use std::collections::VecDeque;
+
+/// Seconds of play the frontends can rewind unless told otherwise
+pub const DEFAULT_REWIND_SECONDS: u32 = 10;
+
+/// Keeps the last `capacity` states pushed, newest first out
+pub struct RewindBuffer {
+    capacity: usize,
+    newest: Option<Vec<u8>>,
+    /// Deltas from each state to the one before it, oldest first
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    /// Creates a buffer holding at most `capacity` states
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            newest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// The most states the buffer holds
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of states that can be popped
+    pub fn len(&self) -> usize {
+        if self.newest.is_some() { self.deltas.len() + 1 } else { 0 }
+    }
+
+    /// Returns true if there is nothing to rewind to
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Bytes used by the stored states
+    pub fn memory_usage(&self) -> usize {
+        self.newest.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+
+    /// Forgets every state
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+
+    /// Adds a state, dropping the oldest one if the buffer is full
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(previous) = self.newest.take() {
+            self.deltas.push_back(encode_delta(&state, &previous));
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.newest = Some(state);
+    }
+
+    /// Removes and returns the newest state
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+        self.newest = self.deltas.pop_back().map(|delta| apply_delta(&newest, &delta));
+        Some(newest)
+    }
+}
+
+/// Encodes `older` relative to `newer`: the length of `older` followed by
+/// the run-length encoded XOR of the two, the shorter one padded with zeros
+fn encode_delta(newer: &[u8], older: &[u8]) -> Vec<u8> {
+    let len = ::std::cmp::max(newer.len(), older.len());
+    let xor: Vec<u8> = (0..len)
+        .map(|idx| newer.get(idx).unwrap_or(&0) ^ older.get(idx).unwrap_or(&0))
+        .collect();
+
+    let mut delta = Vec::new();
+    push_varint(&mut delta, older.len());
+    let mut idx = 0;
+    // pairs of (zero run length, literal length, literal bytes)
+    while idx < xor.len() {
+        let zeros = xor[idx..].iter().take_while(|b| **b == 0).count();
+        idx += zeros;
+        let literal = xor[idx..].iter().take_while(|b| **b != 0).count();
+        push_varint(&mut delta, zeros);
+        push_varint(&mut delta, literal);
+        delta.extend_from_slice(&xor[idx..idx + literal]);
+        idx += literal;
+    }
+    delta
+}
+
+/// Rebuilds the older state from the newer one and the delta
+fn apply_delta(newer: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let older_len = read_varint(delta, &mut pos);
+    let mut older = newer.to_vec();
+    older.resize(::std::cmp::max(older_len, newer.len()), 0);
+
+    let mut idx = 0;
+    while pos < delta.len() {
+        idx += read_varint(delta, &mut pos);
+        let literal = read_varint(delta, &mut pos);
+        for byte in &delta[pos..pos + literal] {
+            older[idx] ^= *byte;
+            idx += 1;
+        }
+        pos += literal;
+    }
+    older.truncate(older_len);
+    older
+}
+
+/// Appends a LEB128 encoded number
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some(byte) = data.get(*pos) {
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
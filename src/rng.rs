@@ -0,0 +1,133 @@
+//! Sources of the random bytes used by Cxkk (RND Vx, byte).
+
+extern crate rand;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Supplies the random bytes of Cxkk
+pub trait RandomSource {
+    /// Returns the next byte, any value from 0 to 255
+    fn next_byte(&mut self) -> u8;
+
+    /// The state to keep in a save state, None if the source can't be saved
+    fn save(&self) -> Option<u64> {
+        None
+    }
+
+    /// Continues from a state returned by `save`
+    fn restore(&mut self, _state: u64) {}
+}
+
+/// A seeded pseudo random generator (SplitMix64). The same seed always
+/// produces the same bytes.
+#[derive(Clone, Debug)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    /// Creates a generator from a seed
+    pub fn new(seed: u64) -> Self {
+        SeededRandom { state: seed }
+    }
+
+    /// Picks a seed that differs from run to run
+    pub fn random_seed() -> u64 {
+        rand::random()
+    }
+
+    /// The internal state, `SeededRandom::new(state)` continues where this
+    /// generator is
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn save(&self) -> Option<u64> {
+        Some(self.state)
+    }
+
+    fn restore(&mut self, state: u64) {
+        self.state = state;
+    }
+}
+
+/// Returns the given bytes in order, e.g. ones recorded by `RecordingRandom`
+/// during an earlier run, and 0 once they run out
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedRandom {
+    values: VecDeque<u8>,
+}
+
+impl ScriptedRandom {
+    /// Creates a source returning `values`
+    pub fn new(values: &[u8]) -> Self {
+        ScriptedRandom { values: values.iter().cloned().collect() }
+    }
+
+    /// Number of bytes left before the source starts returning 0
+    pub fn remaining(&self) -> usize {
+        self.values.len()
+    }
+}
+
+impl RandomSource for ScriptedRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.values.pop_front().unwrap_or(0)
+    }
+}
+
+/// Passes on the bytes of another source and records them. Clones share the
+/// recording, so keep a clone to read it after handing the source to the
+/// interpreter.
+#[derive(Clone)]
+pub struct RecordingRandom<R: RandomSource> {
+    source: Rc<RefCell<R>>,
+    values: Rc<RefCell<Vec<u8>>>,
+}
+
+impl <R: RandomSource> RecordingRandom<R> {
+    /// Records the bytes `source` produces
+    pub fn new(source: R) -> Self {
+        RecordingRandom {
+            source: Rc::new(RefCell::new(source)),
+            values: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// The bytes produced so far, oldest first
+    pub fn values(&self) -> Vec<u8> {
+        self.values.borrow().clone()
+    }
+}
+
+impl <R: RandomSource> RandomSource for RecordingRandom<R> {
+    fn next_byte(&mut self) -> u8 {
+        let value = self.source.borrow_mut().next_byte();
+        self.values.borrow_mut().push(value);
+        value
+    }
+
+    fn save(&self) -> Option<u64> {
+        self.source.borrow().save()
+    }
+
+    fn restore(&mut self, state: u64) {
+        self.source.borrow_mut().restore(state);
+    }
+}
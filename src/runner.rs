@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use backend::{Display, Audio, Input, Command};
+use error::Chip8Error;
+use interpreter::{Interpreter, KEY_COUNT, AUDIO_PATTERN_LENGTH};
+use movie::{self, Movie, MovieFrame};
+use rewind::RewindBuffer;
+use rng::SeededRandom;
+use stats::FrameStats;
+
+/// Frames per second. The timers count down and the display is refreshed
+/// once per frame.
+pub const FRAME_RATE: u32 = 60;
+/// Instructions per second the runner executes unless told otherwise
+pub const DEFAULT_CPU_HZ: u32 = 600;
+/// How far the real time loop may fall behind before it stops catching up
+const MAX_FRAME_LAG: u32 = 5;
+/// Errors `take_errors` holds at most, later ones are dropped
+const MAX_ERRORS: usize = 100;
+
+/// What the runner does with input movies
+enum MovieMode {
+    Off,
+    /// The keypad of each frame is appended to the movie
+    Recording(Movie),
+    /// The keypad comes from frame `next` of the movie instead of the input
+    Playing {
+        movie: Movie,
+        next: usize,
+        stop_on_desync: bool,
+        /// The first frame after which the machine differed from the recording
+        desync: Option<usize>,
+    },
+}
+
+/// Drives an interpreter with a display, audio and input backend.
+///
+/// The runner executes the program in 60 Hz frames: each frame runs the
+/// instructions due at the configured CPU clock, then counts the timers
+/// down once and refreshes the display.
+pub struct Runner<D: Display, A: Audio, I: Input> {
+    display: D,
+    audio: A,
+    input: I,
+    audio_pattern: Option<([u8; AUDIO_PATTERN_LENGTH], u8)>,
+    cpu_hz: u32,
+    /// Frames run in the current second
+    frame_in_second: u32,
+    state_path: Option<PathBuf>,
+    state_slots: HashMap<u8, Vec<u8>>,
+    rewind: RewindBuffer,
+    movie: MovieMode,
+    stats: FrameStats,
+    /// Failures that didn't stop the run, see `take_errors`
+    errors: Vec<Chip8Error>,
+}
+
+impl <D: Display, A: Audio, I: Input> Runner<D, A, I> {
+    /// Creates a runner from the three backends
+    pub fn new(display: D, audio: A, input: I) -> Self {
+        Runner {
+            display,
+            audio,
+            input,
+            audio_pattern: None,
+            cpu_hz: DEFAULT_CPU_HZ,
+            frame_in_second: 0,
+            state_path: None,
+            state_slots: HashMap::new(),
+            rewind: RewindBuffer::new(0),
+            movie: MovieMode::Off,
+            stats: FrameStats::default(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Keeps the last `seconds` of play so that `Command::Rewind` can step
+    /// back through them, 0 turns rewinding off
+    pub fn set_rewind_seconds(&mut self, seconds: u32) {
+        self.rewind = RewindBuffer::new((seconds as usize).saturating_mul(FRAME_RATE as usize));
+    }
+
+    /// Keeps the save state slots in files next to `path` (slot n in `path`
+    /// with the extension `.stn`) so they survive between runs. Without a
+    /// path the slots are only kept in memory.
+    pub fn set_state_path(&mut self, path: PathBuf) {
+        self.state_path = Some(path);
+    }
+
+    /// Sets the number of instructions executed per second (at least 1).
+    /// Clocks that are not a multiple of 60 Hz spread the remainder evenly
+    /// over the frames.
+    pub fn set_cpu_hz(&mut self, hz: u32) {
+        self.cpu_hz = ::std::cmp::max(hz, 1);
+        self.frame_in_second = 0;
+    }
+
+    /// The number of instructions executed per second
+    pub fn cpu_hz(&self) -> u32 {
+        self.cpu_hz
+    }
+
+    /// Restarts the program and records the keypad of every frame from here
+    /// on into a movie, see `take_movie`. Random numbers that are not seeded
+    /// get a seed so the movie can repeat them. Rewinding and loading states
+    /// are ignored while recording, they would break the movie.
+    pub fn record_movie(&mut self, intp: &mut Interpreter) -> Result<(), Chip8Error> {
+        if intp.seed().is_none() {
+            intp.set_seed(SeededRandom::random_seed());
+        }
+        intp.reset();
+        self.frame_in_second = 0;
+        self.movie = MovieMode::Recording(Movie::new(intp, self.cpu_hz)?);
+        Ok(())
+    }
+
+    /// Plays `movie` back: the program is restarted with the movie's
+    /// settings and the keypad follows the movie instead of the input. The
+    /// run stops at the end of the movie. If the machine ever differs from
+    /// the recording, the frame is reported by `movie_desync` and, with
+    /// `stop_on_desync`, the run fails with `Chip8Error::MovieDesync`.
+    pub fn play_movie(&mut self, intp: &mut Interpreter, movie: Movie,
+                      stop_on_desync: bool) -> Result<(), Chip8Error> {
+        movie.start(intp)?;
+        self.set_cpu_hz(movie.cpu_hz);
+        self.rewind.clear();
+        self.movie = MovieMode::Playing { movie, next: 0, stop_on_desync, desync: None };
+        Ok(())
+    }
+
+    /// Stops recording or playing a movie and returns it
+    pub fn take_movie(&mut self) -> Option<Movie> {
+        match ::std::mem::replace(&mut self.movie, MovieMode::Off) {
+            MovieMode::Off => None,
+            MovieMode::Recording(movie) | MovieMode::Playing { movie, .. } => Some(movie),
+        }
+    }
+
+    /// The first frame of the movie being played after which the machine
+    /// differed from the recording, None while they agree
+    pub fn movie_desync(&self) -> Option<usize> {
+        match self.movie {
+            MovieMode::Playing { desync, .. } => desync,
+            _ => None,
+        }
+    }
+
+    /// Returns and forgets the failures that didn't stop the run: save
+    /// state slots that could not be saved or loaded and what the display
+    /// and input backends reported. The runner prints nothing itself, so a
+    /// host shows these the way it wants, if at all.
+    pub fn take_errors(&mut self) -> Vec<Chip8Error> {
+        ::std::mem::take(&mut self.errors)
+    }
+
+    /// How long the frames run so far took
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// The display backend
+    pub fn display(&self) -> &D {
+        &self.display
+    }
+
+    /// The audio backend
+    pub fn audio(&self) -> &A {
+        &self.audio
+    }
+
+    /// The input backend
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    /// Mutable access to the input backend
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Runs the interpreter in real time until the input asks to quit, the
+    /// program exits or it reaches a breakpoint
+    pub fn run(&mut self, intp: &mut Interpreter) -> Result<(), Chip8Error> {
+        self.run_for(intp, usize::MAX).map(|_| ())
+    }
+
+    /// Runs at most `frames` frames in real time like `run`. Returns the
+    /// number of frames run, see `run_frames`.
+    pub fn run_for(&mut self, intp: &mut Interpreter, frames: usize) -> Result<usize, Chip8Error> {
+        self.run_while(intp, frames, || Ok(true))
+    }
+
+    /// Runs at most `frames` frames in real time like `run_for`, calling
+    /// `go_on` after every frame and stopping once it returns false, for
+    /// callers that need to check something of their own between frames.
+    /// Returns the number of frames run.
+    pub fn run_while<F>(&mut self, intp: &mut Interpreter, frames: usize, mut go_on: F) -> Result<usize, Chip8Error>
+        where F: FnMut() -> Result<bool, Chip8Error>
+    {
+        let frame_time = Duration::from_secs(1) / FRAME_RATE;
+        let mut deadline = Instant::now() + frame_time;
+        for frame in 0..frames {
+            if !self.frame(intp)? {
+                return Ok(frame);
+            }
+            if !go_on()? {
+                return Ok(frame + 1);
+            }
+            let now = Instant::now();
+            if now < deadline {
+                thread::sleep(deadline - now);
+            } else if now - deadline > frame_time * MAX_FRAME_LAG {
+                // too slow to keep up (or suspended), carry on from here
+                // instead of rushing through the missed frames
+                deadline = now;
+            }
+            deadline += frame_time;
+        }
+        Ok(frames)
+    }
+
+    /// Runs at most `frames` frames as fast as possible. Returns the number of
+    /// frames run, which is smaller than `frames` if the input asked to quit,
+    /// the program exited or it reached a breakpoint.
+    pub fn run_frames(&mut self, intp: &mut Interpreter, frames: usize) -> Result<usize, Chip8Error> {
+        for frame in 0..frames {
+            if !self.frame(intp)? {
+                return Ok(frame);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Polls input, runs one frame of the interpreter, then updates the tone
+    /// and the display. Returns false if the input asked to quit, the program
+    /// exited, it reached a breakpoint or the movie being played ended.
+    fn frame(&mut self, intp: &mut Interpreter) -> Result<bool, Chip8Error> {
+        let mut rewind = false;
+        let mut reset = false;
+        let in_movie = !matches!(self.movie, MovieMode::Off);
+        for command in self.input.poll() {
+            match command {
+                Command::Quit => return Ok(false),
+                // a movie being played decides when the program restarts
+                Command::Reset => reset = !self.is_playing(),
+                Command::Rewind | Command::LoadState(_) if in_movie => {},
+                Command::Rewind => rewind = true,
+                Command::SaveState(slot) => {
+                    if let Err(e) = self.save_slot(intp, slot) {
+                        self.add_error(Chip8Error::StateSlot { slot, error: Box::new(e) });
+                    }
+                },
+                Command::LoadState(slot) => {
+                    if let Err(e) = self.load_slot(intp, slot) {
+                        self.add_error(Chip8Error::StateSlot { slot, error: Box::new(e) });
+                    }
+                },
+            }
+        }
+        for error in self.input.take_errors() {
+            self.add_error(error);
+        }
+        let mut keys = (0..KEY_COUNT)
+            .filter(|key| self.input.is_pressed(*key))
+            .fold(0u16, |keys, key| keys | 1 << key);
+        if let MovieMode::Playing { ref movie, next, .. } = self.movie {
+            match movie.frames().get(next) {
+                Some(frame) => {
+                    keys = frame.keys;
+                    reset = frame.reset;
+                },
+                None => return Ok(false),
+            }
+        }
+        if reset {
+            intp.reset();
+            self.rewind.clear();
+        }
+        for key in 0..KEY_COUNT {
+            intp.set_key(key, keys & (1 << key) != 0);
+        }
+
+        let started = Instant::now();
+        if rewind {
+            if let Some(state) = self.rewind.pop() {
+                intp.load_state(&state)?;
+                // keep the oldest state so holding the key stays there
+                if self.rewind.is_empty() {
+                    self.rewind.push(state);
+                }
+            }
+        } else {
+            if self.rewind.capacity() > 0 {
+                self.rewind.push(intp.save_state());
+            }
+            let cycles = self.cycles_for_frame();
+            // a frame that crashed is recorded too, so the movie reproduces it
+            let result = intp.run_frame(cycles);
+            let checked = self.movie_frame(intp, keys, reset);
+            result?;
+            checked?;
+        }
+        let run = started.elapsed();
+        let present = self.update_backends(intp);
+        for error in self.display.take_errors() {
+            self.add_error(error);
+        }
+        self.stats.add_frame(run, present);
+        Ok(!intp.is_halted() && intp.pause().is_none())
+    }
+
+    /// Brings the tone and the display up to date with the interpreter, for
+    /// callers that step it themselves
+    pub fn refresh(&mut self, intp: &mut Interpreter) {
+        self.update_backends(intp);
+    }
+
+    /// Updates the tone and presents the screen if it changed, returns how
+    /// long presenting took
+    fn update_backends(&mut self, intp: &mut Interpreter) -> Option<Duration> {
+        if let Some((pattern, pitch)) = intp.audio_pattern() {
+            if self.audio_pattern != Some((*pattern, pitch)) {
+                self.audio.set_pattern(pattern, pitch);
+                self.audio_pattern = Some((*pattern, pitch));
+            }
+        }
+        self.audio.set_tone(intp.sound_active());
+        if !intp.framebuffer().is_dirty() {
+            return None;
+        }
+        let started = Instant::now();
+        self.display.present(intp.framebuffer());
+        intp.framebuffer_mut().clear_dirty();
+        Some(started.elapsed())
+    }
+
+    fn add_error(&mut self, error: Chip8Error) {
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(error);
+        }
+    }
+
+    fn is_playing(&self) -> bool {
+        matches!(self.movie, MovieMode::Playing { .. })
+    }
+
+    /// Records the frame that just ran, or checks it against the movie
+    /// being played
+    fn movie_frame(&mut self, intp: &Interpreter, keys: u16, reset: bool) -> Result<(), Chip8Error> {
+        match self.movie {
+            MovieMode::Off => {},
+            MovieMode::Recording(ref mut movie) => {
+                movie.push(MovieFrame { keys, reset, checksum: movie::checksum(intp) });
+            },
+            MovieMode::Playing { ref movie, ref mut next, stop_on_desync, ref mut desync } => {
+                let frame = *next;
+                *next += 1;
+                if desync.is_none() && movie.frames()[frame].checksum != movie::checksum(intp) {
+                    *desync = Some(frame);
+                    if stop_on_desync {
+                        return Err(Chip8Error::MovieDesync { frame });
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn slot_path(&self, slot: u8) -> Option<PathBuf> {
+        self.state_path.as_ref().map(|path| path.with_extension(format!("st{}", slot)))
+    }
+
+    fn save_slot(&mut self, intp: &Interpreter, slot: u8) -> Result<(), Chip8Error> {
+        let state = intp.save_state();
+        if let Some(path) = self.slot_path(slot) {
+            File::create(path)?.write_all(&state)?;
+        }
+        self.state_slots.insert(slot, state);
+        Ok(())
+    }
+
+    /// Restores a slot, an empty slot leaves the machine alone
+    fn load_slot(&mut self, intp: &mut Interpreter, slot: u8) -> Result<(), Chip8Error> {
+        if !self.state_slots.contains_key(&slot) {
+            if let Some(path) = self.slot_path(slot).filter(|path| path.exists()) {
+                let mut state = Vec::new();
+                File::open(path)?.read_to_end(&mut state)?;
+                self.state_slots.insert(slot, state);
+            }
+        }
+        match self.state_slots.get(&slot) {
+            Some(state) => intp.load_state(state),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of instructions to run in the next frame so that on average
+    /// `cpu_hz` instructions run per second
+    fn cycles_for_frame(&mut self) -> usize {
+        let hz = self.cpu_hz as u64;
+        let frame = self.frame_in_second as u64;
+        let done = frame * hz / FRAME_RATE as u64;
+        let due = (frame + 1) * hz / FRAME_RATE as u64;
+        self.frame_in_second = (self.frame_in_second + 1) % FRAME_RATE;
+        (due - done) as usize
+    }
+}
@@ -0,0 +1,161 @@
+//! Writes the framebuffer as an image file, without any image library: PNG
+//! with the frontend colors or black and white PBM.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use error::Chip8Error;
+use framebuffer::{Framebuffer, PALETTE};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// Largest block of uncompressed data a deflate stream can hold
+const MAX_STORED_BLOCK: usize = 0xffff;
+
+/// Writes the framebuffer to `path`, as PNG or PBM depending on the
+/// extension. Each pixel becomes a `scale` x `scale` square.
+pub fn save_screenshot(framebuffer: &Framebuffer, path: &Path, scale: u32) -> Result<(), Chip8Error> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_lowercase);
+    let png = match extension.as_deref() {
+        Some("png") => true,
+        Some("pbm") => false,
+        _ => {
+            let message = format!("{}: screenshots are .png or .pbm files", path.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+        },
+    };
+    let mut file = BufWriter::new(File::create(path)?);
+    if png {
+        write_png(framebuffer, scale, &mut file)?;
+    } else {
+        write_pbm(framebuffer, scale, &mut file)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes the framebuffer as an 8-bit indexed PNG, in the colors of
+/// `PALETTE`
+pub fn write_png<W: Write>(framebuffer: &Framebuffer, scale: u32, output: &mut W) -> io::Result<()> {
+    let (width, height, rows) = scaled_rows(framebuffer, scale);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&be32(width));
+    header.extend_from_slice(&be32(height));
+    // 8 bits per pixel, palette colors, deflate, no filters, no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    let palette: Vec<u8> = PALETTE.iter().flat_map(|&(r, g, b)| vec![r, g, b]).collect();
+
+    let mut image = Vec::with_capacity(rows.len() * (width as usize + 1));
+    for row in &rows {
+        // filter type 0, the row as is
+        image.push(0);
+        image.extend_from_slice(row);
+    }
+
+    output.write_all(&PNG_SIGNATURE)?;
+    write_chunk(output, b"IHDR", &header)?;
+    write_chunk(output, b"PLTE", &palette)?;
+    write_chunk(output, b"IDAT", &zlib_stored(&image))?;
+    write_chunk(output, b"IEND", &[])
+}
+
+/// Writes the framebuffer as a binary PBM (P4), every pixel lit on any
+/// plane black
+pub fn write_pbm<W: Write>(framebuffer: &Framebuffer, scale: u32, output: &mut W) -> io::Result<()> {
+    let (width, height, rows) = scaled_rows(framebuffer, scale);
+    write!(output, "P4\n{} {}\n", width, height)?;
+    for row in &rows {
+        let mut packed = vec![0u8; row.len().div_ceil(8)];
+        for (x, pixel) in row.iter().enumerate() {
+            if *pixel != 0 {
+                packed[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        output.write_all(&packed)?;
+    }
+    Ok(())
+}
+
+/// The width, height and rows of pixel values of the scaled image
+fn scaled_rows(framebuffer: &Framebuffer, scale: u32) -> (u32, u32, Vec<Vec<u8>>) {
+    let scale = ::std::cmp::max(scale, 1) as usize;
+    let width = framebuffer.width() as usize;
+    let mut rows = Vec::with_capacity(framebuffer.height() as usize * scale);
+    for row in framebuffer.pixels().chunks(width) {
+        let scaled: Vec<u8> = row.iter()
+            .flat_map(|pixel| ::std::iter::repeat_n(*pixel & 0x3, scale))
+            .collect();
+        for _ in 0..scale {
+            rows.push(scaled.clone());
+        }
+    }
+    ((width * scale) as u32, rows.len() as u32, rows)
+}
+
+fn write_chunk<W: Write>(output: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    output.write_all(&be32(data.len() as u32))?;
+    output.write_all(kind)?;
+    output.write_all(data)?;
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    output.write_all(&be32(crc.finish()))
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32 KiB window, no preset dictionary, fastest level
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&be32(adler32(data)));
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// The CRC-32 of PNG chunks (the one of zip and ethernet)
+struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        Crc32 { crc: 0xffff_ffff }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.crc ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (self.crc & 1).wrapping_neg();
+                self.crc = (self.crc >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+fn be32(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
@@ -0,0 +1,371 @@
+extern crate sdl2;
+
+use backend::{Display, Audio, Input, Command};
+use error::Chip8Error;
+use framebuffer::{Framebuffer, SCREEN_WIDTH, SCREEN_HEIGHT, PALETTE};
+use interpreter::{KEY_COUNT, AUDIO_PATTERN_LENGTH};
+use gamepad::{Gamepads, PadButton, PadEvent};
+use keymap::Keymap;
+use runner::Runner;
+
+use self::sdl2::render::{Renderer, Texture};
+use self::sdl2::event::Event;
+use self::sdl2::keyboard;
+use self::sdl2::keyboard::{Keycode, Scancode};
+use self::sdl2::{VideoSubsystem, AudioSubsystem, GameControllerSubsystem, EventPump};
+use self::sdl2::controller::{Axis, Button, GameController};
+use self::sdl2::audio::{AudioDevice, AudioCallback, AudioSpecDesired};
+use self::sdl2::pixels::{Color, PixelFormatEnum};
+
+static DEFAULT_WINDOW_TITLE: &str = "pschip8";
+const DEFAULT_VIDEO_SCALE: u8 = 8;
+
+static DESIRED_AUDIO_SPEC: AudioSpecDesired = AudioSpecDesired {
+    freq: Some(44100),
+    channels: Some(1),
+    samples: Some(2048),
+};
+
+/// Host key that rewinds while held
+const REWIND_SCANCODE: Scancode = Scancode::Backspace;
+
+/// Shows the framebuffer in an SDL window. The window keeps its size when
+/// the program switches resolution, pixels are scaled to fill it.
+///
+/// The framebuffer is converted to RGB into a streaming texture of its size,
+/// which the renderer copies scaled to the whole window in one go.
+pub struct SdlDisplay {
+    /// Texture of the framebuffer's resolution, created on the first present
+    texture: Option<Texture>,
+    /// The RGB pixels uploaded to the texture
+    rgb: Vec<u8>,
+    renderer: Renderer<'static>,
+    /// Frames that could not be drawn since `take_errors`
+    errors: Vec<Chip8Error>,
+}
+
+impl SdlDisplay {
+    fn new(video_sys: &VideoSubsystem) -> Result<Self, Chip8Error> {
+        let window = video_sys.window(DEFAULT_WINDOW_TITLE,
+                            SCREEN_WIDTH as u32 * DEFAULT_VIDEO_SCALE as u32,
+                            SCREEN_HEIGHT as u32 * DEFAULT_VIDEO_SCALE as u32).build().map_err(backend_error)?;
+
+        Ok(SdlDisplay {
+            texture: None,
+            rgb: Vec::new(),
+            renderer: window.renderer().present_vsync().build().map_err(backend_error)?,
+            errors: Vec::new(),
+        })
+    }
+
+    /// Converts the framebuffer to RGB and uploads it to the texture, which
+    /// is recreated when the resolution changes
+    fn upload(&mut self, framebuffer: &Framebuffer) -> Result<&Texture, String> {
+        let (width, height) = (framebuffer.width() as u32, framebuffer.height() as u32);
+        let resized = !self.texture.as_ref().is_some_and(|texture| {
+            let query = texture.query();
+            (query.width, query.height) == (width, height)
+        });
+        if resized {
+            let texture = self.renderer.create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+                .map_err(|e| format!("{:?}", e))?;
+            self.texture = Some(texture);
+        }
+
+        self.rgb.clear();
+        for pixel in framebuffer.pixels() {
+            let (r, g, b) = PALETTE[*pixel as usize & 0x3];
+            self.rgb.extend_from_slice(&[r, g, b]);
+        }
+        let texture = self.texture.as_mut().unwrap();
+        texture.update(None, &self.rgb, width as usize * 3).map_err(|e| format!("{:?}", e))?;
+        Ok(texture)
+    }
+}
+
+impl Display for SdlDisplay {
+    fn present(&mut self, framebuffer: &Framebuffer) {
+        if let Err(e) = self.upload(framebuffer) {
+            self.errors.push(Chip8Error::Backend(format!("could not draw the screen: {}", e)));
+            return;
+        }
+        self.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        self.renderer.clear();
+        self.renderer.copy(self.texture.as_ref().unwrap(), None, None);
+        self.renderer.present();
+    }
+
+    fn take_errors(&mut self) -> Vec<Chip8Error> {
+        ::std::mem::take(&mut self.errors)
+    }
+}
+
+struct Tone {
+    freq: f32,
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+    pattern: Option<[u8; AUDIO_PATTERN_LENGTH]>,
+}
+
+impl Tone {
+    /// Plays the XO-CHIP pattern instead of the square wave
+    fn set_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_LENGTH], pitch: u8) {
+        let bits = (AUDIO_PATTERN_LENGTH * 8) as f32;
+        let rate = 4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0);
+        self.pattern = Some(*pattern);
+        // phase runs over the whole pattern once per period
+        self.phase_inc = rate / bits / self.freq;
+    }
+}
+
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            let high = match self.pattern {
+                Some(ref pattern) => {
+                    let bit = (self.phase * (AUDIO_PATTERN_LENGTH * 8) as f32) as usize;
+                    (pattern[bit / 8] >> (7 - bit % 8)) & 0x1 == 1
+                },
+                None => self.phase <= 0.5,
+            };
+            *x = if high { self.volume } else { -self.volume };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+/// Plays a square wave tone on the default SDL audio device
+pub struct SdlAudio {
+    au_dev: AudioDevice<Tone>,
+}
+
+impl SdlAudio {
+    fn new(au_sys: &AudioSubsystem) -> Result<Self, Chip8Error> {
+        Ok(SdlAudio {
+            au_dev: au_sys.open_playback(None, &DESIRED_AUDIO_SPEC, |spec| {
+                Tone {
+                    freq: spec.freq as f32,
+                    phase_inc: 440.0 / spec.freq as f32,
+                    phase: 0.0,
+                    volume: 0.5,
+                    pattern: None,
+                }
+            }).map_err(backend_error)?,
+        })
+    }
+}
+
+impl Audio for SdlAudio {
+    fn set_tone(&mut self, on: bool) {
+        if on {
+            self.au_dev.resume();
+        } else {
+            self.au_dev.pause();
+        }
+    }
+
+    fn set_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_LENGTH], pitch: u8) {
+        self.au_dev.lock().set_pattern(pattern, pitch);
+    }
+}
+
+/// Reads the hex keypad from the keyboard and game controllers through a
+/// `Keymap`, the COSMAC layout unless set otherwise. Controllers can be
+/// plugged in and out while the program runs. Closing the window quits,
+/// Shift+R restarts the program, Shift+F1 - Shift+F9 save the machine to a
+/// slot and F1 - F9 restore it. Holding Backspace rewinds.
+pub struct SdlInput {
+    event_pump: EventPump,
+    /// None if SDL has no controller support on this system
+    controller_sys: Option<GameControllerSubsystem>,
+    /// The controllers connected, SDL only reports the ones kept open
+    controllers: Vec<GameController>,
+    gamepads: Gamepads,
+    keymap: Keymap,
+    /// Host keys for each key of the hex keypad, indexed by the hex key
+    scancodes: Vec<Vec<Scancode>>,
+    keys: [bool; KEY_COUNT as usize],
+    /// Controllers that could not be opened since `take_errors`
+    errors: Vec<Chip8Error>,
+}
+
+impl SdlInput {
+    fn new(event_pump: EventPump, controller_sys: Option<GameControllerSubsystem>) -> Result<Self, Chip8Error> {
+        let mut input = SdlInput {
+            event_pump,
+            controller_sys,
+            controllers: Vec::new(),
+            gamepads: Gamepads::new(),
+            keymap: Keymap::default(),
+            scancodes: Vec::new(),
+            keys: [false; KEY_COUNT as usize],
+            errors: Vec::new(),
+        };
+        input.set_keymap(&Keymap::default())?;
+        Ok(input)
+    }
+
+    /// Reads the keypad through `keymap`. Fails, keeping the keymap in use,
+    /// if it names a key SDL doesn't know.
+    pub fn set_keymap(&mut self, keymap: &Keymap) -> Result<(), Chip8Error> {
+        let mut scancodes = Vec::with_capacity(KEY_COUNT as usize);
+        for key in 0..KEY_COUNT {
+            // controller buttons are looked up by `Gamepads`
+            let host_keys = keymap.host_keys(key).iter()
+                .filter(|name| PadButton::from_name(name).is_none())
+                .map(|name| {
+                    Scancode::from_name(name)
+                        .ok_or_else(|| Chip8Error::InvalidKeymap(format!("unknown key '{}'", name)))
+                });
+            scancodes.push(host_keys.collect::<Result<Vec<Scancode>, Chip8Error>>()?);
+        }
+        self.scancodes = scancodes;
+        self.keymap = keymap.clone();
+        Ok(())
+    }
+
+    /// Opens a controller SDL reported as connected, by its device index
+    fn open_controller(&mut self, index: i32) {
+        let controller = match self.controller_sys {
+            Some(ref controller_sys) => controller_sys.open(index as u32),
+            None => return,
+        };
+        match controller {
+            Ok(controller) => {
+                self.gamepads.handle(PadEvent::Added(controller.instance_id()));
+                self.controllers.push(controller);
+            },
+            Err(e) => {
+                let message = format!("could not open game controller {}: {}", index, e);
+                self.errors.push(Chip8Error::Backend(message));
+            },
+        }
+    }
+
+    /// Forgets a controller that was disconnected, by its instance id
+    fn close_controller(&mut self, id: i32) {
+        self.controllers.retain(|controller| controller.instance_id() != id);
+        self.gamepads.handle(PadEvent::Removed(id));
+    }
+}
+
+impl Input for SdlInput {
+    fn poll(&mut self) -> Vec<Command> {
+        let mut commands = Vec::new();
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit{..} => commands.push(Command::Quit),
+                // restart
+                Event::KeyDown{keycode: Some(Keycode::R), keymod: km, ..}
+                    if km.intersects(keyboard::LSHIFTMOD | keyboard::RSHIFTMOD) => {
+                    commands.push(Command::Reset);
+                },
+                Event::ControllerDeviceAdded{which, ..} => self.open_controller(which),
+                Event::ControllerDeviceRemoved{which, ..} => self.close_controller(which),
+                Event::ControllerButtonDown{which, button, ..} => {
+                    self.gamepads.handle(PadEvent::Button { pad: which, button: pad_button(button), pressed: true });
+                },
+                Event::ControllerButtonUp{which, button, ..} => {
+                    self.gamepads.handle(PadEvent::Button { pad: which, button: pad_button(button), pressed: false });
+                },
+                Event::ControllerAxisMotion{which, axis: Axis::LeftX, value, ..} => {
+                    self.gamepads.handle(PadEvent::Stick { pad: which, horizontal: true, value });
+                },
+                Event::ControllerAxisMotion{which, axis: Axis::LeftY, value, ..} => {
+                    self.gamepads.handle(PadEvent::Stick { pad: which, horizontal: false, value });
+                },
+                Event::KeyDown{keycode: Some(keycode), keymod: km, repeat: false, ..} => {
+                    if let Some(slot) = state_slot(keycode) {
+                        if km.intersects(keyboard::LSHIFTMOD | keyboard::RSHIFTMOD) {
+                            commands.push(Command::SaveState(slot));
+                        } else {
+                            commands.push(Command::LoadState(slot));
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let pad_keys = self.gamepads.hex_keys(&self.keymap);
+        let keyboard_state = self.event_pump.keyboard_state();
+        for (key, scancodes) in self.scancodes.iter().enumerate() {
+            self.keys[key] = pad_keys & (1 << key) != 0
+                || scancodes.iter().any(|sc| keyboard_state.is_scancode_pressed(*sc));
+        }
+        if keyboard_state.is_scancode_pressed(REWIND_SCANCODE) {
+            commands.push(Command::Rewind);
+        }
+        commands
+    }
+
+    fn is_pressed(&self, key: u8) -> bool {
+        key < KEY_COUNT && self.keys[key as usize]
+    }
+
+    fn take_errors(&mut self) -> Vec<Chip8Error> {
+        ::std::mem::take(&mut self.errors)
+    }
+}
+
+/// The controller button as `Gamepads` knows it
+fn pad_button(button: Button) -> PadButton {
+    match button {
+        Button::A => PadButton::A,
+        Button::B => PadButton::B,
+        Button::X => PadButton::X,
+        Button::Y => PadButton::Y,
+        Button::Back => PadButton::Back,
+        Button::Guide => PadButton::Guide,
+        Button::Start => PadButton::Start,
+        Button::LeftStick => PadButton::LeftStick,
+        Button::RightStick => PadButton::RightStick,
+        Button::LeftShoulder => PadButton::LeftShoulder,
+        Button::RightShoulder => PadButton::RightShoulder,
+        Button::DPadUp => PadButton::Up,
+        Button::DPadDown => PadButton::Down,
+        Button::DPadLeft => PadButton::Left,
+        Button::DPadRight => PadButton::Right,
+    }
+}
+
+/// The save state slot of a function key, F1 being slot 1
+fn state_slot(keycode: Keycode) -> Option<u8> {
+    let slot = match keycode {
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        Keycode::F5 => 5,
+        Keycode::F6 => 6,
+        Keycode::F7 => 7,
+        Keycode::F8 => 8,
+        Keycode::F9 => 9,
+        _ => return None,
+    };
+    Some(slot)
+}
+
+/// A runner that uses the SDL window, audio device and keyboard
+pub type SdlFrontend = Runner<SdlDisplay, SdlAudio, SdlInput>;
+
+/// Opens the window and audio device and creates a runner for them
+pub fn frontend() -> Result<SdlFrontend, Chip8Error> {
+    let sdl_ctxt = sdl2::init().map_err(backend_error)?;
+    let au_sys = sdl_ctxt.audio().map_err(backend_error)?;
+    let vd_sys = sdl_ctxt.video().map_err(backend_error)?;
+    // controllers are optional, the keyboard still works without them
+    let controller_sys = sdl_ctxt.game_controller().ok();
+    let evt_pump = sdl_ctxt.event_pump().map_err(backend_error)?;
+
+    Ok(Runner::new(SdlDisplay::new(&vd_sys)?, SdlAudio::new(&au_sys)?, SdlInput::new(evt_pump, controller_sys)?))
+}
+
+/// Wraps an SDL error into a backend initialization error
+fn backend_error<E: ::std::fmt::Display>(e: E) -> Chip8Error {
+    Chip8Error::BackendInit(e.to_string())
+}
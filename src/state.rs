@@ -0,0 +1,167 @@
+//! The binary save state format.
+//!
+//! A state starts with a header: the magic bytes, the format version and
+//! the FNV-1a hash of the program it was saved from. The machine follows,
+//! every multi-byte value big-endian, in the order `Interpreter::save_state`
+//! writes it.
+
+use error::Chip8Error;
+
+/// Bytes every save state starts with
+pub const STATE_MAGIC: &[u8; 8] = b"PSC8STAT";
+/// Version of the save state format written by this interpreter
+pub const STATE_VERSION: u16 = 1;
+
+/// FNV-1a hash of a program, identifies the program a state belongs to
+pub fn rom_hash(program: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in program {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Appends values to a save state
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Starts a state with the header for `program`
+    pub fn new(program: &[u8]) -> Self {
+        let mut writer = StateWriter::default();
+        writer.bytes(STATE_MAGIC);
+        writer.u16(STATE_VERSION);
+        writer.u64(rom_hash(program));
+        writer
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&[(value >> 8) as u8, value as u8]);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.u16((value >> 16) as u16);
+        self.u16(value as u16);
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.u32((value >> 32) as u32);
+        self.u32(value as u32);
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// A length followed by the bytes
+    pub fn block(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.bytes(bytes);
+    }
+
+    /// A presence flag followed by the value if there is one
+    pub fn option_u64(&mut self, value: Option<u64>) {
+        self.bool(value.is_some());
+        self.u64(value.unwrap_or(0));
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads values back from a save state, failing on truncated data
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    /// Makes the error for data that is not valid
+    invalid: fn(&str) -> Chip8Error,
+}
+
+impl <'a> StateReader<'a> {
+    /// Checks the header against `program` and returns a reader positioned
+    /// after it
+    pub fn new(data: &'a [u8], program: &[u8]) -> Result<Self, Chip8Error> {
+        let mut reader = StateReader::without_header(data, invalid);
+        if reader.bytes(STATE_MAGIC.len()).ok() != Some(&STATE_MAGIC[..]) {
+            return Err(invalid("not a save state"));
+        }
+        let version = reader.u16()?;
+        if version != STATE_VERSION {
+            return Err(invalid(&format!("unsupported save state version {}", version)));
+        }
+        if reader.u64()? != rom_hash(program) {
+            return Err(invalid("the state was saved from a different program"));
+        }
+        Ok(reader)
+    }
+
+    /// Reads other formats written with a `StateWriter`, which start with
+    /// their own header, failing with the errors `invalid` makes
+    pub fn without_header(data: &'a [u8], invalid: fn(&str) -> Chip8Error) -> Self {
+        StateReader { data, invalid }
+    }
+
+    /// Returns true once everything was read
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, Chip8Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let bytes = self.bytes(2)?;
+        Ok(((bytes[0] as u16) << 8) | bytes[1] as u16)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Chip8Error> {
+        Ok(((self.u16()? as u32) << 16) | self.u16()? as u32)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Chip8Error> {
+        Ok(((self.u32()? as u64) << 32) | self.u32()? as u64)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if len > self.data.len() {
+            return Err((self.invalid)("the data is truncated"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    /// Reads a `StateWriter::block`
+    pub fn block(&mut self) -> Result<&'a [u8], Chip8Error> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// Reads a `StateWriter::option_u64`
+    pub fn option_u64(&mut self) -> Result<Option<u64>, Chip8Error> {
+        let present = self.bool()?;
+        let value = self.u64()?;
+        Ok(if present { Some(value) } else { None })
+    }
+}
+
+/// Error for a state that can't be loaded
+pub fn invalid(reason: &str) -> Chip8Error {
+    Chip8Error::InvalidState(reason.to_string())
+}
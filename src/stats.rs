@@ -0,0 +1,77 @@
+//! Frame time statistics: how long the interpreter and the display take per
+//! frame, to tell which of the two a slow run spends its time in.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Time spent in the frames run so far
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    frames: u32,
+    presented: u32,
+    run_time: Duration,
+    present_time: Duration,
+    slowest_frame: Duration,
+}
+
+impl FrameStats {
+    /// Counts a frame that ran the interpreter for `run` and, if the screen
+    /// changed, presented it in `present`
+    pub fn add_frame(&mut self, run: Duration, present: Option<Duration>) {
+        self.frames += 1;
+        self.run_time += run;
+        let mut frame = run;
+        if let Some(present) = present {
+            self.presented += 1;
+            self.present_time += present;
+            frame += present;
+        }
+        self.slowest_frame = ::std::cmp::max(self.slowest_frame, frame);
+    }
+
+    /// Number of frames run
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Number of frames that presented the screen, the others left it as it
+    /// was
+    pub fn presented(&self) -> u32 {
+        self.presented
+    }
+
+    /// Average time the interpreter took per frame
+    pub fn average_run(&self) -> Duration {
+        average(self.run_time, self.frames)
+    }
+
+    /// Average time presenting the screen took, over the frames that did
+    pub fn average_present(&self) -> Duration {
+        average(self.present_time, self.presented)
+    }
+
+    /// The longest frame, running and presenting
+    pub fn slowest_frame(&self) -> Duration {
+        self.slowest_frame
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} frames, {} presented; run {:.3} ms, present {:.3} ms on average; slowest frame {:.3} ms",
+               self.frames, self.presented, millis(self.average_run()), millis(self.average_present()),
+               millis(self.slowest_frame))
+    }
+}
+
+fn average(total: Duration, count: u32) -> Duration {
+    if count == 0 {
+        Duration::default()
+    } else {
+        total / count
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
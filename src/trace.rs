@@ -0,0 +1,319 @@
+//! Execution traces: a record of every instruction the interpreter runs.
+//!
+//! The interpreter hands a `TraceEntry` to its `Tracer` after each
+//! instruction. The tracers here write entries as text lines, in a compact
+//! binary form that `TraceReader` reads back, or keep the last few in memory
+//! to show what led up to a crash. `FilteredTrace` passes on only the entries
+//! of an address range or of some opcode classes.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use disasm::mnemonic;
+use error::Chip8Error;
+
+/// Bytes a binary trace starts with
+pub const TRACE_MAGIC: &[u8; 8] = b"PSC8TRCE";
+/// Version of the binary trace format written by this interpreter
+pub const TRACE_VERSION: u16 = 2;
+
+/// The machine around one executed instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Address of the instruction
+    pub pc: u16,
+    pub opcode: u16,
+    /// The word after the opcode, the address F000 nnnn loads
+    pub next: u16,
+    /// V0 - VF before the instruction ran
+    pub before: [u8; 16],
+    /// V0 - VF after it ran
+    pub after: [u8; 16],
+    /// I, the stack pointer and the timers after it ran
+    pub i: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl TraceEntry {
+    /// Bit x is set if the instruction changed Vx
+    pub fn changed(&self) -> u16 {
+        (0..16).filter(|x| self.before[*x] != self.after[*x]).fold(0, |mask, x| mask | 1 << x)
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    /// `PC: OPCODE  MNEMONIC  CHANGED REGISTERS  I SP DT ST`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = mnemonic(self.opcode, Some(self.next))
+            .unwrap_or_else(|| format!("dw 0x{:04X}", self.opcode));
+        let changed: Vec<String> = (0..16)
+            .filter(|x| self.changed() & (1 << x) != 0)
+            .map(|x| format!("V{:X}={:02X}", x, self.after[x]))
+            .collect();
+        let changed = if changed.is_empty() { "-".to_string() } else { changed.join(" ") };
+        write!(f, "{:03X}: {:04X}  {:<22} {:<24} I={:03X} SP={:X} DT={:02X} ST={:02X}",
+               self.pc, self.opcode, text, changed, self.i, self.sp, self.dt, self.st)
+    }
+}
+
+/// Receives an entry after each instruction
+pub trait Tracer {
+    /// Records an instruction, an error stops the interpreter
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()>;
+}
+
+/// Writes each entry as a line of text
+pub struct TextTrace<W: Write> {
+    output: W,
+}
+
+impl <W: Write> TextTrace<W> {
+    pub fn new(output: W) -> Self {
+        TextTrace { output }
+    }
+}
+
+impl <W: Write> Tracer for TextTrace<W> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        writeln!(self.output, "{}", entry)
+    }
+}
+
+/// Writes entries in a compact binary form, about 14 bytes an instruction.
+///
+/// After the magic bytes and the format version come the entries, each the
+/// PC and opcode, the next word only for F000 nnnn, a mask of the registers
+/// that differ from the previous entry's values (all zero before the first
+/// entry) followed by their values before the instruction ran, a mask of the
+/// registers the instruction changed followed by their new values, then I,
+/// SP, DT and ST, multi-byte values big-endian. The first mask is only set
+/// before the first entry and when a `FilteredTrace` skipped instructions.
+pub struct BinaryTrace<W: Write> {
+    output: W,
+    /// V0 - VF after the last entry written
+    registers: [u8; 16],
+}
+
+impl <W: Write> BinaryTrace<W> {
+    /// Writes the header to `output`
+    pub fn new(mut output: W) -> io::Result<Self> {
+        output.write_all(TRACE_MAGIC)?;
+        output.write_all(&be16(TRACE_VERSION))?;
+        Ok(BinaryTrace { output, registers: [0; 16] })
+    }
+}
+
+impl <W: Write> Tracer for BinaryTrace<W> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&be16(entry.pc));
+        data.extend_from_slice(&be16(entry.opcode));
+        if entry.opcode == 0xf000 {
+            data.extend_from_slice(&be16(entry.next));
+        }
+        let skipped = (0..16).filter(|x| self.registers[*x] != entry.before[*x]).fold(0, |mask, x| mask | 1 << x);
+        data.extend_from_slice(&be16(skipped));
+        data.extend((0..16).filter(|x| skipped & (1 << x) != 0).map(|x| entry.before[x]));
+        let changed = entry.changed();
+        data.extend_from_slice(&be16(changed));
+        data.extend((0..16).filter(|x| changed & (1 << x) != 0).map(|x| entry.after[x]));
+        data.extend_from_slice(&be16(entry.i));
+        data.extend_from_slice(&[entry.sp, entry.dt, entry.st]);
+        self.registers = entry.after;
+        self.output.write_all(&data)
+    }
+}
+
+/// Reads the entries of a `BinaryTrace` back
+pub struct TraceReader<R: Read> {
+    input: R,
+    /// V0 - VF after the last entry read
+    registers: [u8; 16],
+}
+
+impl <R: Read> TraceReader<R> {
+    /// Checks the header of the trace in `input`
+    pub fn new(mut input: R) -> Result<Self, Chip8Error> {
+        let mut header = [0; 10];
+        input.read_exact(&mut header).map_err(|_| invalid("not a trace"))?;
+        if &header[..8] != TRACE_MAGIC {
+            return Err(invalid("not a trace"));
+        }
+        let version = ((header[8] as u16) << 8) | header[9] as u16;
+        if version != TRACE_VERSION {
+            return Err(invalid(&format!("unsupported trace version {}", version)));
+        }
+        Ok(TraceReader { input, registers: [0; 16] })
+    }
+
+    fn read_entry(&mut self) -> Result<Option<TraceEntry>, Chip8Error> {
+        let mut first = [0];
+        if self.input.read(&mut first)? == 0 {
+            return Ok(None);
+        }
+        let mut low = [0];
+        self.read(&mut low)?;
+        let pc = ((first[0] as u16) << 8) | low[0] as u16;
+        let opcode = self.u16()?;
+        let next = if opcode == 0xf000 { self.u16()? } else { 0 };
+        let mut before = self.registers;
+        self.read_registers(&mut before)?;
+        let mut after = before;
+        self.read_registers(&mut after)?;
+        let i = self.u16()?;
+        let mut rest = [0; 3];
+        self.read(&mut rest)?;
+        self.registers = after;
+        Ok(Some(TraceEntry {
+            pc,
+            opcode,
+            next,
+            before,
+            after,
+            i,
+            sp: rest[0],
+            dt: rest[1],
+            st: rest[2],
+        }))
+    }
+
+    /// Reads a mask and the values of the registers it has set
+    fn read_registers(&mut self, registers: &mut [u8; 16]) -> Result<(), Chip8Error> {
+        let mask = self.u16()?;
+        for (x, register) in registers.iter_mut().enumerate() {
+            if mask & (1 << x) != 0 {
+                let mut value = [0];
+                self.read(&mut value)?;
+                *register = value[0];
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<(), Chip8Error> {
+        self.input.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid("the trace is truncated"),
+            _ => e.into(),
+        })
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let mut bytes = [0; 2];
+        self.read(&mut bytes)?;
+        Ok(((bytes[0] as u16) << 8) | bytes[1] as u16)
+    }
+}
+
+impl <R: Read> Iterator for TraceReader<R> {
+    type Item = Result<TraceEntry, Chip8Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_entry().transpose()
+    }
+}
+
+/// Keeps only the last entries, to show what led up to a crash. Clones share
+/// the entries, so keep a clone to dump them after handing the tracer to the
+/// interpreter.
+#[derive(Clone)]
+pub struct RingTrace {
+    capacity: usize,
+    entries: Rc<RefCell<VecDeque<TraceEntry>>>,
+}
+
+impl RingTrace {
+    /// Keeps the last `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        RingTrace {
+            capacity,
+            entries: Rc::new(RefCell::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// The entries kept, oldest first
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.entries.borrow().iter().cloned().collect()
+    }
+
+    /// Writes the entries kept as text lines, oldest first
+    pub fn dump<W: Write>(&self, mut output: W) -> io::Result<()> {
+        for entry in self.entries.borrow().iter() {
+            writeln!(output, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl Tracer for RingTrace {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut entries = self.entries.borrow_mut();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(*entry);
+        Ok(())
+    }
+}
+
+/// Which instructions a `FilteredTrace` passes on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// First and last address traced
+    pub start: u16,
+    pub end: u16,
+    /// Bit n is set to trace the opcodes starting with the hex digit n, e.g.
+    /// bit 0xd for the Dxyn draws
+    pub classes: u16,
+}
+
+impl Default for TraceFilter {
+    /// Traces every instruction
+    fn default() -> Self {
+        TraceFilter { start: 0, end: 0xffff, classes: 0xffff }
+    }
+}
+
+impl TraceFilter {
+    /// Returns true if the entry passes the filter
+    pub fn matches(&self, entry: &TraceEntry) -> bool {
+        let class = entry.opcode >> 12;
+        self.start <= entry.pc && entry.pc <= self.end && self.classes & (1 << class) != 0
+    }
+}
+
+/// Passes on the entries a filter lets through to another tracer
+pub struct FilteredTrace<T: Tracer> {
+    filter: TraceFilter,
+    inner: T,
+}
+
+impl <T: Tracer> FilteredTrace<T> {
+    pub fn new(filter: TraceFilter, inner: T) -> Self {
+        FilteredTrace { filter, inner }
+    }
+}
+
+impl <T: Tracer> Tracer for FilteredTrace<T> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        if self.filter.matches(entry) {
+            self.inner.record(entry)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn be16(value: u16) -> [u8; 2] {
+    [(value >> 8) as u8, value as u8]
+}
+
+fn invalid(reason: &str) -> Chip8Error {
+    Chip8Error::InvalidTrace(reason.to_string())
+}
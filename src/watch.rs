@@ -0,0 +1,265 @@
+//! Watchpoints on memory and conditions on registers, both of which pause
+//! `Interpreter::run_frame` after the instruction that set them off.
+
+use std::fmt;
+use std::str::FromStr;
+use interpreter::Interpreter;
+
+/// How an instruction touched memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Data read: Dxyn sprites, Fx65, 5xy3 and F002
+    Read,
+    /// Data written: Fx33, Fx55 and 5xy2
+    Write,
+    /// Instruction fetched, including the address word of F000 nnnn
+    Fetch,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Fetch => "fetch",
+        })
+    }
+}
+
+/// Watches the addresses `start` through `end` for the enabled accesses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub fetch: bool,
+}
+
+impl Watchpoint {
+    /// Watches `start` through `end` for writes only
+    pub fn writes(start: u16, end: u16) -> Self {
+        Watchpoint { start, end, read: false, write: true, fetch: false }
+    }
+
+    /// Watches `start` through `end` for data reads only
+    pub fn reads(start: u16, end: u16) -> Self {
+        Watchpoint { start, end, read: true, write: false, fetch: false }
+    }
+
+    /// Watches `start` through `end` for instruction fetches only
+    pub fn fetches(start: u16, end: u16) -> Self {
+        Watchpoint { start, end, read: false, write: false, fetch: true }
+    }
+
+    /// Returns true if the watchpoint covers `access` to `address`
+    pub fn matches(&self, access: Access, address: usize) -> bool {
+        let enabled = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Fetch => self.fetch,
+        };
+        enabled && self.start as usize <= address && address <= self.end as usize
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kinds: Vec<&str> = [(self.read, "read"), (self.write, "write"), (self.fetch, "fetch")]
+            .iter()
+            .filter(|kind| kind.0)
+            .map(|kind| kind.1)
+            .collect();
+        write!(f, "{} 0x{:03X}", kinds.join("/"), self.start)?;
+        if self.end != self.start {
+            write!(f, "-0x{:03X}", self.end)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    /// Parses what `Display` writes: the accesses joined with `/`, then the
+    /// address or an inclusive `START-END` range, such as `read/write 0x300-0x30F`
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut words = text.split_whitespace();
+        let (kinds, range) = match (words.next(), words.next(), words.next()) {
+            (Some(kinds), Some(range), None) => (kinds, range),
+            _ => return Err(format!("'{}' is not ACCESSES ADDRESS[-END]", text)),
+        };
+        let mut watchpoint = Watchpoint { start: 0, end: 0, read: false, write: false, fetch: false };
+        for kind in kinds.split('/') {
+            match kind.to_lowercase().as_str() {
+                "read" => watchpoint.read = true,
+                "write" => watchpoint.write = true,
+                "fetch" => watchpoint.fetch = true,
+                _ => return Err(format!("unknown access '{}'", kind)),
+            }
+        }
+        let (start, end) = match range.find('-') {
+            Some(at) => (parse_address(&range[..at])?, parse_address(&range[at + 1..])?),
+            None => (parse_address(range)?, parse_address(range)?),
+        };
+        if end < start {
+            return Err(format!("'{}' ends before it starts", range));
+        }
+        watchpoint.start = start;
+        watchpoint.end = end;
+        Ok(watchpoint)
+    }
+}
+
+/// A register a condition looks at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    /// V0 through VF
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+impl Register {
+    /// The current value of the register
+    pub fn value(&self, intp: &Interpreter) -> u16 {
+        match *self {
+            Register::V(x) => intp.register(x).unwrap_or(0) as u16,
+            Register::I => intp.index(),
+            Register::Pc => intp.pc(),
+            Register::Sp => intp.call_stack().len() as u16,
+            Register::Dt => intp.delay_timer() as u16,
+            Register::St => intp.sound_timer() as u16,
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let name = text.to_uppercase();
+        let register = match name.as_str() {
+            "I" => Register::I,
+            "PC" => Register::Pc,
+            "SP" => Register::Sp,
+            "DT" => Register::Dt,
+            "ST" => Register::St,
+            _ => name.strip_prefix('V')
+                .filter(|x| x.len() == 1)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .map(Register::V)
+                .ok_or_else(|| format!("unknown register '{}'", text))?,
+        };
+        Ok(register)
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => f.write_str("I"),
+            Register::Pc => f.write_str("PC"),
+            Register::Sp => f.write_str("SP"),
+            Register::Dt => f.write_str("DT"),
+            Register::St => f.write_str("ST"),
+        }
+    }
+}
+
+/// How a condition compares the register with its value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match *self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+/// A comparison of a register with a value, such as `VF == 1` or
+/// `I > 0xE00`. It pauses the program when it turns true.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    /// Returns true if the condition holds for the interpreter's registers
+    pub fn holds(&self, intp: &Interpreter) -> bool {
+        let register = self.register.value(intp);
+        match self.comparison {
+            Comparison::Eq => register == self.value,
+            Comparison::Ne => register != self.value,
+            Comparison::Lt => register < self.value,
+            Comparison::Le => register <= self.value,
+            Comparison::Gt => register > self.value,
+            Comparison::Ge => register >= self.value,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    /// Parses `REGISTER OP VALUE`, the spaces are optional and the value is
+    /// decimal or hex with a 0x prefix
+    fn from_str(text: &str) -> Result<Self, String> {
+        // two character operators first so that "<=" isn't taken for "<"
+        let comparisons = [Comparison::Eq, Comparison::Ne, Comparison::Le, Comparison::Ge,
+                           Comparison::Lt, Comparison::Gt];
+        let (comparison, at) = comparisons.iter()
+            .filter_map(|comparison| text.find(comparison.symbol()).map(|at| (*comparison, at)))
+            .next()
+            .ok_or_else(|| format!("'{}' has no comparison (==, !=, <, <=, > or >=)", text))?;
+        let register = text[..at].trim().parse()?;
+        let value = parse_address(text[at + comparison.symbol().len()..].trim())?;
+        Ok(Condition { register, comparison, value })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} 0x{:X}", self.register, self.comparison.symbol(), self.value)
+    }
+}
+
+/// Parses a number written in decimal or, with a 0x prefix, in hex. The
+/// debugger and the command line read their numbers with it.
+pub fn parse_number(text: &str) -> Result<usize, String> {
+    let parsed = if text.starts_with("0x") || text.starts_with("0X") {
+        usize::from_str_radix(&text[2..], 16)
+    } else {
+        text.parse()
+    };
+    parsed.map_err(|_| format!("'{}' is not a number", text))
+}
+
+/// Parses a `parse_number` that fits in 16 bits
+pub fn parse_address(text: &str) -> Result<u16, String> {
+    let value = parse_number(text)?;
+    if value > 0xffff {
+        return Err(format!("'{}' is not an address", text));
+    }
+    Ok(value as u16)
+}
@@ -0,0 +1,59 @@
+extern crate pschip8;
+
+mod common;
+
+/// Runs `program` followed by a check of every `(x, value)`: an `SE Vx,
+/// value` skipping a jump to an endless loop. An exit follows the checks, so
+/// they all passed if the interpreter exited.
+fn registers_hold(program: &[u16], expected: &[(u8, u8)]) -> bool {
+    let mut code = program.to_vec();
+    let fail = 0x200 + 2 * (code.len() + 2 * expected.len() + 1) as u16;
+    for &(x, value) in expected {
+        code.push(0x3000 | (x as u16) << 8 | value as u16);
+        code.push(0x1000 | fail);
+    }
+    code.push(0x00fd);
+    code.push(0x1000 | fail);
+
+    let mut intp = common::load(&code);
+    intp.run_frame(100).unwrap();
+    intp.is_halted()
+}
+
+#[test]
+fn the_check_fails_on_other_values() {
+    assert!(registers_hold(&[0x6001], &[(0, 1)]));
+    assert!(!registers_hold(&[0x6001], &[(0, 2)]));
+}
+
+#[test]
+fn add_byte_leaves_vf_alone() {
+    // VF = 5, V0 = 0xff + 2
+    assert!(registers_hold(&[0x6f05, 0x60ff, 0x7002], &[(0, 1), (0xf, 5)]));
+}
+
+#[test]
+fn sub_sets_vf_without_a_borrow() {
+    // V0 = 7 - 7
+    assert!(registers_hold(&[0x6007, 0x6107, 0x8015], &[(0, 0), (0xf, 1)]));
+    // V0 = 5 - 3
+    assert!(registers_hold(&[0x6005, 0x6103, 0x8015], &[(0, 2), (0xf, 1)]));
+}
+
+#[test]
+fn sub_wraps_on_a_borrow() {
+    // V0 = 3 - 5
+    assert!(registers_hold(&[0x6003, 0x6105, 0x8015], &[(0, 0xfe), (0xf, 0)]));
+    // V0 = V1 - V0 = 3 - 5
+    assert!(registers_hold(&[0x6005, 0x6103, 0x8017], &[(0, 0xfe), (0xf, 0)]));
+    // V0 = 4 - 4
+    assert!(registers_hold(&[0x6004, 0x6104, 0x8017], &[(0, 0), (0xf, 1)]));
+}
+
+#[test]
+fn sne_reg_skips_or_falls_through() {
+    // V0 == V1, V2 = 5 runs
+    assert!(registers_hold(&[0x6001, 0x6101, 0x9010, 0x6205], &[(2, 5)]));
+    // V0 != V1, V2 = 5 is skipped
+    assert!(registers_hold(&[0x6001, 0x6102, 0x9010, 0x6205, 0x6306], &[(2, 0), (3, 6)]));
+}
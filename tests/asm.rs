@@ -0,0 +1,73 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::{assemble, disassemble, Chip8Error, Quirks};
+
+fn assemble_error(source: &str) -> (usize, String) {
+    match assemble(source) {
+        Err(Chip8Error::Assemble { line, message, .. }) => (line, message),
+        other => panic!("expected an assembly error, got {:?}", other),
+    }
+}
+
+#[test]
+fn labels_constants_and_data() {
+    let source = "\
+SPEED equ 2 + 2
+start:
+    LD I, ball      ; 0x200
+    ADD V0, SPEED
+    JP start
+ball: db 0x60, 0xf0
+    dw ball + 1
+";
+    assert_eq!(assemble(source).unwrap(),
+               vec![0xa2, 0x06, 0x70, 0x04, 0x12, 0x00, 0x60, 0xf0, 0x02, 0x07]);
+}
+
+#[test]
+fn errors_name_the_line() {
+    assert_eq!(assemble_error("CLS\nLD V0, 0x100").0, 2);
+    assert!(assemble_error("JP nowhere").1.contains("nowhere"));
+    assert!(assemble_error("a:\na: CLS").1.contains("already defined"));
+    assert!(assemble_error("CLS\norg 0x300").1.contains("org"));
+}
+
+#[test]
+fn org_moves_the_labels() {
+    let source = "    org 0x600\nloop: JP loop\n";
+    assert_eq!(assemble(source).unwrap(), vec![0x16, 0x00]);
+    assert_eq!(assemble(&disassemble(&[0x16, 0x00], 0x600)).unwrap(), vec![0x16, 0x00]);
+}
+
+#[test]
+fn one_operand_shifts_shift_vx_under_every_quirk() {
+    let program = assemble("LD V0, 0x10\nLD V1, 6\nSHR V1\nLD V2, 0x81\nSHL V2").unwrap();
+    assert_eq!(&program[4..6], &[0x81, 0x16]);
+    assert_eq!(&program[8..], &[0x82, 0x2e]);
+    let words: Vec<u16> = program.chunks(2).map(|word| (word[0] as u16) << 8 | word[1] as u16).collect();
+    for quirks in &[Quirks::cosmac_vip(), Quirks::chip48()] {
+        let mut intp = common::load(&words);
+        intp.set_quirks(*quirks).unwrap();
+        intp.run_frame(5).unwrap();
+        assert_eq!(intp.register(1), Some(3));
+        assert_eq!(intp.register(2), Some(2));
+        // the bit shifted out of V2
+        assert_eq!(intp.register(0xf), Some(1));
+    }
+}
+
+#[test]
+fn every_word_disassembles_to_source_that_assembles_back() {
+    for word in 0..=0xffffu16 {
+        let bytes = [(word >> 8) as u8, word as u8];
+        for &origin in &[0x200, 0x400] {
+            let source = disassemble(&bytes, origin);
+            match assemble(&source) {
+                Ok(program) => assert_eq!(program, bytes, "{:04X} at 0x{:03X}: {}", word, origin, source),
+                Err(e) => panic!("{:04X} at 0x{:03X}: {} does not assemble: {}", word, origin, source, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Fixtures shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use pschip8::Interpreter;
+
+/// The Breakout hack in `programs/`, keys 4 and 6 move the paddle
+pub const BREAKOUT: &str = "Breakout_Brix_hack_David_Winter_1997.ch8";
+
+/// The path of a program in `programs/`
+pub fn program_path(rom: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("programs").join(rom)
+}
+
+/// An interpreter with `program` loaded and the default settings
+pub fn load(program: &[u16]) -> Interpreter {
+    let mut intp = Interpreter::new();
+    intp.load_from_bytes(program).unwrap();
+    intp
+}
+
+/// An interpreter with Breakout loaded and the default settings
+pub fn breakout() -> Interpreter {
+    let mut intp = Interpreter::new();
+    intp.load_program_from_file(&program_path(BREAKOUT)).unwrap();
+    intp
+}
@@ -0,0 +1,101 @@
+extern crate pschip8;
+
+mod common;
+
+use std::time::{Duration, Instant};
+use pschip8::backend::{NullAudio, NullDisplay, NullInput};
+use pschip8::{Debugger, Interpreter, Runner, Watchpoint};
+
+#[test]
+fn continue_stops_after_the_frame_limit() {
+    // loops forever at 0x202
+    let mut intp = common::load(&[0x7001, 0x1202]);
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    let mut debugger = Debugger::new();
+    debugger.set_frame_limit(Some(3));
+
+    let mut output = Vec::new();
+    assert!(debugger.execute(&mut runner, &mut intp, "continue", &mut output).unwrap());
+    assert!(String::from_utf8(output).unwrap().contains("stopped after 3 frames"));
+    assert_eq!(intp.pc(), 0x202);
+
+    // a count overrides the limit
+    let mut output = Vec::new();
+    assert!(debugger.execute(&mut runner, &mut intp, "c 2", &mut output).unwrap());
+    assert!(String::from_utf8(output).unwrap().contains("stopped after 2 frames"));
+}
+
+#[test]
+fn headless_continue_does_not_wait_for_real_time() {
+    let mut intp = common::load(&[0x7001, 0x1202]);
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    let mut debugger = Debugger::new();
+    debugger.set_frame_limit(Some(600));
+    debugger.set_headless(true);
+
+    // 10 seconds of the program
+    let started = Instant::now();
+    let mut output = Vec::new();
+    assert!(debugger.execute(&mut runner, &mut intp, "continue", &mut output).unwrap());
+    assert!(String::from_utf8(output).unwrap().contains("stopped after 600 frames"));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn stepping_counts_the_timers_down() {
+    // sets the delay timer to 3 and loops at 0x204 until it runs out
+    let mut intp = common::load(&[0x6003, 0xf015, 0xf007, 0x3000, 0x1204, 0x120a]);
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    let mut debugger = Debugger::new();
+
+    // 10 instructions a frame at the default clock
+    let mut output = Vec::new();
+    debugger.execute(&mut runner, &mut intp, "step 10", &mut output).unwrap();
+    assert_eq!(intp.delay_timer(), 2);
+    debugger.execute(&mut runner, &mut intp, "step 30", &mut output).unwrap();
+    assert_eq!(intp.delay_timer(), 0);
+    assert_eq!(intp.pc(), 0x20a);
+}
+
+/// Runs debugger commands on a program that never ends, returns the output
+fn commands(intp: &mut Interpreter, lines: &[&str]) -> String {
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    let mut debugger = Debugger::new();
+    let mut output = Vec::new();
+    for line in lines {
+        assert!(debugger.execute(&mut runner, intp, line, &mut output).unwrap());
+    }
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn watch_commands_add_the_watchpoints_they_name() {
+    let mut intp = common::load(&[0x1200]);
+    let output = commands(&mut intp, &["watch 0x300", "rwatch 0x300 0x30F", "awatch 16 0x20",
+                                       "xwatch 0x200-0x2FF"]);
+    assert_eq!(intp.watchpoints(), &[
+        Watchpoint::writes(0x300, 0x300),
+        Watchpoint::reads(0x300, 0x30f),
+        Watchpoint { read: true, ..Watchpoint::writes(16, 0x20) },
+        Watchpoint::fetches(0x200, 0x2ff),
+    ]);
+    assert!(output.contains("watchpoint 2: read/write 0x010-0x020"), "{}", output);
+
+    let output = commands(&mut intp, &["watch 0x301 0x300", "watch 0x10000", "watch zero", "watch"]);
+    assert_eq!(output.matches("error: ").count(), 4, "{}", output);
+    assert_eq!(intp.watchpoints().len(), 4);
+}
+
+#[test]
+fn set_takes_the_register_names_of_conditions() {
+    let mut intp = common::load(&[0x1200]);
+    let output = commands(&mut intp, &["set vf 0x20", "set I 0xFFF", "set pc 0x300", "set dt 7"]);
+    assert_eq!(intp.register(0xf), Some(0x20));
+    assert_eq!(intp.index(), 0xfff);
+    assert_eq!(intp.pc(), 0x300);
+    assert_eq!(intp.delay_timer(), 7);
+    assert!(output.starts_with("VF = 0x20\n"), "{}", output);
+
+    let output = commands(&mut intp, &["set V10 1", "set v0 0x100", "set sp 1"]);
+    assert_eq!(output.matches("error: ").count(), 3, "{}", output);
+}
@@ -0,0 +1,25 @@
+extern crate pschip8;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use pschip8::{assemble, disassemble};
+
+#[test]
+fn bundled_programs_disassemble_to_source_that_assembles_back() {
+    for name in &["Breakout_Brix_hack_David_Winter_1997.ch8", "Chip8_Picture.ch8",
+                  "Chip8_emulator_Logo_Garstyciuks.ch8"] {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("programs").join(name);
+        let mut program = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut program).unwrap();
+        let source = disassemble(&program, 0x200);
+        assert_eq!(assemble(&source).unwrap(), program, "{}", name);
+    }
+}
+
+#[test]
+fn jump_targets_get_labels() {
+    let source = disassemble(&[0x22, 0x04, 0x12, 0x02, 0x00, 0xee, 0x13, 0x00], 0x200);
+    let lines: Vec<&str> = source.lines().map(|line| line.split(';').next().unwrap().trim_end()).collect();
+    assert_eq!(lines, ["    CALL L204", "L202:", "    JP L202", "L204:", "    RET", "    JP 0x300"]);
+}
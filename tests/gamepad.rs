@@ -0,0 +1,124 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::backend::{Command, Input, NullAudio, NullDisplay};
+use pschip8::{Chip8Error, Gamepads, Keymap, KeymapConfig, PadButton, PadEvent, Runner, STICK_DEAD_ZONE};
+
+fn press(pads: &mut Gamepads, pad: i32, button: PadButton, pressed: bool) {
+    pads.handle(PadEvent::Button { pad, button, pressed });
+}
+
+#[test]
+fn buttons_press_hex_keys_through_the_keymap() {
+    let keymap = Keymap::cosmac();
+    let mut pads = Gamepads::new();
+    pads.handle(PadEvent::Added(0));
+    assert_eq!(pads.connected(), 1);
+    assert_eq!(pads.hex_keys(&keymap), 0);
+
+    press(&mut pads, 0, PadButton::Left, true);
+    press(&mut pads, 0, PadButton::A, true);
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x4 | 1 << 0x5);
+    press(&mut pads, 0, PadButton::Left, false);
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x5);
+    // unmapped buttons press nothing
+    press(&mut pads, 0, PadButton::Start, true);
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x5);
+}
+
+#[test]
+fn the_stick_presses_directions_past_the_dead_zone() {
+    let keymap = Keymap::cosmac();
+    let mut pads = Gamepads::new();
+    pads.handle(PadEvent::Stick { pad: 3, horizontal: true, value: STICK_DEAD_ZONE });
+    assert_eq!(pads.hex_keys(&keymap), 0);
+    pads.handle(PadEvent::Stick { pad: 3, horizontal: true, value: 30000 });
+    pads.handle(PadEvent::Stick { pad: 3, horizontal: false, value: -30000 });
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x6 | 1 << 0x2);
+    assert!(pads.is_held(PadButton::Right));
+
+    // the D-pad and the stick don't release each other
+    press(&mut pads, 3, PadButton::Right, true);
+    pads.handle(PadEvent::Stick { pad: 3, horizontal: true, value: 0 });
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x6 | 1 << 0x2);
+}
+
+#[test]
+fn unplugging_releases_what_the_controller_held() {
+    let keymap = Keymap::cosmac();
+    let mut pads = Gamepads::new();
+    pads.handle(PadEvent::Added(0));
+    pads.handle(PadEvent::Added(1));
+    press(&mut pads, 0, PadButton::Up, true);
+    press(&mut pads, 1, PadButton::Down, true);
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x2 | 1 << 0x8);
+
+    pads.handle(PadEvent::Removed(0));
+    assert_eq!(pads.connected(), 1);
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x8);
+    // plugged back in, it starts with nothing held
+    pads.handle(PadEvent::Added(0));
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x8);
+}
+
+#[test]
+fn mappings_change_per_program() {
+    let config = "\
+[Breakout_Brix_hack_David_Winter_1997.ch8]
+4 = Q, pad left, Pad LeftShoulder
+6 = E, Pad Right
+5 =
+";
+    let mut pads = Gamepads::new();
+    press(&mut pads, 0, PadButton::LeftShoulder, true);
+    press(&mut pads, 0, PadButton::A, true);
+
+    let mut keymap = Keymap::cosmac();
+    KeymapConfig::parse(config, "Breakout_Brix_hack_David_Winter_1997.ch8").unwrap().apply(&mut keymap);
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x4);
+
+    let mut keymap = Keymap::cosmac();
+    KeymapConfig::parse(config, "Chip8_Picture.ch8").unwrap().apply(&mut keymap);
+    assert_eq!(pads.hex_keys(&keymap), 1 << 0x5);
+}
+
+#[test]
+fn button_names() {
+    assert_eq!(PadButton::from_name("pad start"), Some(PadButton::Start));
+    assert_eq!(PadButton::Up.name(), "Pad Up");
+    assert_eq!(PadButton::from_name("Start"), None);
+}
+
+/// An input whose controller fails to open on the first poll
+#[derive(Default)]
+struct FailingInput {
+    failed: bool,
+}
+
+impl Input for FailingInput {
+    fn poll(&mut self) -> Vec<Command> {
+        Vec::new()
+    }
+
+    fn is_pressed(&self, _key: u8) -> bool {
+        false
+    }
+
+    fn take_errors(&mut self) -> Vec<Chip8Error> {
+        if self.failed {
+            return Vec::new();
+        }
+        self.failed = true;
+        vec![Chip8Error::Backend("could not open game controller 0".to_string())]
+    }
+}
+
+#[test]
+fn backend_failures_reach_the_runner() {
+    let mut intp = common::load(&[0x1200]);
+    let mut runner = Runner::new(NullDisplay, NullAudio, FailingInput::default());
+    assert_eq!(runner.run_frames(&mut intp, 3).unwrap(), 3);
+    let errors: Vec<String> = runner.take_errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(errors, vec!["frontend error: could not open game controller 0"]);
+}
@@ -0,0 +1,207 @@
+extern crate pschip8;
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+use pschip8::backend::{NullDisplay, NullAudio, NullInput};
+use pschip8::{GdbServer, Runner};
+
+/// A GDB client talking to a stub serving `program` on a loopback port
+struct Client {
+    stream: TcpStream,
+    server: thread::JoinHandle<()>,
+}
+
+impl Client {
+    fn connect(program: &'static [u16]) -> Client {
+        let (sender, receiver) = mpsc::channel();
+        let server = thread::spawn(move || {
+            let mut intp = common::load(program);
+            let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+            let server = GdbServer::bind("127.0.0.1:0").unwrap();
+            sender.send(server.local_addr().unwrap()).unwrap();
+            server.serve(&mut runner, &mut intp).unwrap();
+        });
+        let stream = TcpStream::connect(receiver.recv().unwrap()).unwrap();
+        Client { stream, server }
+    }
+
+    /// Sends a packet and returns the reply
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        assert_eq!(self.read_byte(), b'+');
+        self.reply()
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+    }
+
+    fn reply(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    /// Detaches and waits for the stub to finish
+    fn detach(mut self) {
+        assert_eq!(self.request("D"), "OK");
+        self.server.join().unwrap();
+    }
+}
+
+static PROGRAM: [u16; 6] = [
+    0x6005, // 0x200: LD V0, 0x05
+    0x6107, // 0x202: LD V1, 0x07
+    0x8014, // 0x204: ADD V0, V1
+    0xa300, // 0x206: LD I, 0x300
+    0xf033, // 0x208: LD B, V0
+    0x120a, // 0x20a: JP 0x20a
+];
+
+#[test]
+fn registers_and_memory() {
+    let mut client = Client::connect(&PROGRAM);
+    assert_eq!(client.request("?"), "S05");
+
+    let registers = client.request("g");
+    // V0 - VF, I, PC, SP, DT, ST
+    assert_eq!(registers.len(), (16 + 2 + 2 + 3) * 2);
+    assert_eq!(&registers[32..40], "00000200");
+
+    assert_eq!(client.request("P3=7f"), "OK");
+    assert_eq!(client.request("p3"), "7f");
+    assert_eq!(client.request("P10=0123"), "OK");
+    assert_eq!(client.request("p10"), "0123");
+    assert_eq!(client.request("P12=11"), "E01");
+    assert_eq!(client.request("p15"), "E01");
+
+    assert_eq!(client.request("m200,4"), "60056107");
+    assert_eq!(client.request("M300,2:abcd"), "OK");
+    assert_eq!(client.request("m300,2"), "abcd");
+    assert_eq!(client.request("mffe,4"), "0000");
+    assert_eq!(client.request("m1000,1"), "E01");
+    client.detach();
+}
+
+#[test]
+fn breakpoints_step_and_watchpoints() {
+    let mut client = Client::connect(&PROGRAM);
+    assert_eq!(client.request("Z0,204,2"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p11"), "0204");
+    assert_eq!(client.request("z0,204,2"), "OK");
+
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0206");
+    assert_eq!(client.request("p0"), "0c");
+
+    assert_eq!(client.request("Z2,300,3"), "OK");
+    assert_eq!(client.request("c"), "T05watch:300;");
+    assert_eq!(client.request("p11"), "020a");
+    assert_eq!(client.request("m300,3"), "000102");
+    client.detach();
+}
+
+static COPY: [u16; 4] = [
+    0xa300, // 0x200: LD I, 0x300
+    0xf033, // 0x202: LD B, V0
+    0xf265, // 0x204: LD V2, [I]
+    0x1206, // 0x206: JP 0x206
+];
+
+#[test]
+fn watchpoint_kinds_in_stop_replies() {
+    let mut client = Client::connect(&COPY);
+    assert_eq!(client.request("Z4,301,1"), "OK");
+    assert_eq!(client.request("c"), "T05awatch:301;");
+    assert_eq!(client.request("p11"), "0204");
+    assert_eq!(client.request("z4,301,1"), "OK");
+
+    assert_eq!(client.request("Z3,302,1"), "OK");
+    assert_eq!(client.request("Z2,302,1"), "OK");
+    assert_eq!(client.request("c"), "T05rwatch:302;");
+    assert_eq!(client.request("p11"), "0206");
+    client.detach();
+}
+
+#[test]
+fn interrupt_and_no_ack_mode() {
+    let mut client = Client::connect(&PROGRAM);
+    assert_eq!(client.request("QStartNoAckMode"), "OK");
+    client.send("c");
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+
+    // without acknowledgements a bad checksum is not answered with '-'
+    client.stream.write_all(b"$p11#00").unwrap();
+    assert_eq!(client.reply(), "020a");
+    client.send("D");
+    assert_eq!(client.reply(), "OK");
+    client.server.join().unwrap();
+}
+
+#[test]
+fn bad_checksums_are_rejected() {
+    let mut client = Client::connect(&PROGRAM);
+    client.stream.write_all(b"$g#00").unwrap();
+    assert_eq!(client.read_byte(), b'-');
+    assert_eq!(client.request("p11"), "0200");
+    client.detach();
+}
+
+#[test]
+fn malformed_lengths_are_errors() {
+    let mut client = Client::connect(&PROGRAM);
+    assert_eq!(client.request("Mffffffffffffffff,2:abcd"), "E01");
+    assert_eq!(client.request("Mfff,2:abcd"), "E01");
+    // a 64 KiB watchpoint is cut at the end of memory
+    assert_eq!(client.request("Z2,300,10000"), "OK");
+    assert_eq!(client.request("z2,300,10000"), "OK");
+    assert_eq!(client.request("Z2,300,0"), "OK");
+    client.detach();
+}
+
+static DELAY: [u16; 6] = [
+    0x6020, // 0x200: LD V0, 0x20
+    0xf015, // 0x202: LD DT, V0
+    0xf107, // 0x204: LD V1, DT
+    0x3100, // 0x206: SE V1, 0x00
+    0x1204, // 0x208: JP 0x204
+    0x120a, // 0x20a: JP 0x20a
+];
+
+#[test]
+fn packets_sent_while_running_are_kept() {
+    let mut client = Client::connect(&DELAY);
+    assert_eq!(client.request("QStartNoAckMode"), "OK");
+    client.send("Z0,20a,2");
+    assert_eq!(client.reply(), "OK");
+    // the packet arrives while the delay runs down
+    client.send("c");
+    client.send("p11");
+    assert_eq!(client.reply(), "S05");
+    assert_eq!(client.reply(), "020a");
+    client.send("D");
+    assert_eq!(client.reply(), "OK");
+    client.server.join().unwrap();
+}
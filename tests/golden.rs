@@ -0,0 +1,123 @@
+//! Runs the programs in `programs/` without a window and compares the screen
+//! they end up with against the goldens in `tests/golden/`.
+//!
+//! Run with `BLESS=1` to write the current screens as the new goldens, then
+//! review the changes before committing them.
+
+extern crate pschip8;
+
+mod common;
+
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::PathBuf;
+use pschip8::backend::{NullAudio, NullDisplay, ScriptedInput};
+use pschip8::{Interpreter, Quirks, Framebuffer, Runner};
+
+/// How to run one program
+struct Case {
+    /// File in `programs/`, the golden has the same name with `.txt`
+    rom: &'static str,
+    /// Quirks preset, one of `PRESET_NAMES`
+    quirks: &'static str,
+    seed: u64,
+    /// Frames run at the default 600 Hz before the screen is compared
+    frames: usize,
+    /// Key presses: the frame, the hex key and whether it goes down or up
+    input: &'static [(usize, u8, bool)],
+}
+
+fn check(case: &Case) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut intp = Interpreter::new();
+    intp.set_quirks(Quirks::preset(case.quirks).expect("unknown quirks preset")).unwrap();
+    intp.set_seed(case.seed);
+    intp.load_program_from_file(&common::program_path(case.rom)).unwrap();
+
+    let mut input = ScriptedInput::default();
+    let mut keys = 0u16;
+    for frame in 0..case.frames {
+        for &(_, key, pressed) in case.input.iter().filter(|event| event.0 == frame) {
+            keys = if pressed { keys | 1 << key } else { keys & !(1 << key) };
+        }
+        input.push_frame(keys);
+    }
+    // stops early if the program exits
+    let mut runner = Runner::new(NullDisplay, NullAudio, input);
+    runner.run_frames(&mut intp, case.frames).unwrap_or_else(|e| panic!("{}: {}", case.rom, e));
+
+    let actual = render(intp.framebuffer());
+    let golden = root.join("tests/golden").join(case.rom).with_extension("txt");
+    if env::var_os("BLESS").is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden)
+        .unwrap_or_else(|e| panic!("{}: {}, run with BLESS=1 to create it", golden.display(), e));
+    if actual != expected {
+        panic!("{}: the screen differs from {} (rows marked with >), run with BLESS=1 if the \
+                change is expected\n{}", case.rom, golden.display(), diff(&expected, &actual));
+    }
+}
+
+/// The screen as text: the size, then a line per row with `.` for unlit
+/// pixels and the color (1 - 3) of lit ones
+fn render(framebuffer: &Framebuffer) -> String {
+    let width = framebuffer.width() as usize;
+    let mut text = format!("{}x{}\n", width, framebuffer.height());
+    for row in framebuffer.pixels().chunks(width) {
+        text.extend(row.iter().map(|pixel| match pixel & 0x3 {
+            0 => '.',
+            color => (b'0' + color) as char,
+        }));
+        text.push('\n');
+    }
+    text
+}
+
+/// The actual screen with the rows that differ from the golden marked
+fn diff(expected: &str, actual: &str) -> String {
+    let mut text = String::new();
+    let mut expected = expected.lines();
+    for line in actual.lines() {
+        let marker = if expected.next() == Some(line) { ' ' } else { '>' };
+        let _ = writeln!(text, "{} {}", marker, line);
+    }
+    text
+}
+
+#[test]
+fn chip8_emulator_logo() {
+    check(&Case {
+        rom: "Chip8_emulator_Logo_Garstyciuks.ch8",
+        quirks: "default",
+        seed: 1,
+        frames: 60,
+        input: &[],
+    });
+}
+
+#[test]
+fn chip8_picture() {
+    check(&Case {
+        rom: "Chip8_Picture.ch8",
+        quirks: "default",
+        seed: 1,
+        frames: 60,
+        input: &[],
+    });
+}
+
+#[test]
+fn breakout() {
+    check(&Case {
+        rom: common::BREAKOUT,
+        quirks: "vip",
+        seed: 1,
+        frames: 300,
+        // the paddle goes right, then left
+        input: &[(30, 6, true), (60, 6, false), (90, 4, true), (150, 4, false)],
+    });
+}
@@ -0,0 +1,13 @@
+extern crate pschip8;
+
+use pschip8::Instruction;
+
+#[test]
+fn every_opcode_encodes_back_to_itself() {
+    for opcode in 0..=0xffffu16 {
+        if let Ok(instruction) = Instruction::decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{:?}", instruction);
+            assert_eq!(Instruction::decode(instruction.encode()), Ok(instruction));
+        }
+    }
+}
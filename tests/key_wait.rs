@@ -0,0 +1,78 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::{Interpreter, Quirks};
+
+/// V0 = 30, delay timer = V0, V1 = key (Fx0A at 0x204), then loops at 0x206
+fn key_wait(quirks: Quirks) -> Interpreter {
+    let mut intp = common::load(&[0x601e, 0xf015, 0xf10a, 0x1206]);
+    intp.set_quirks(quirks).unwrap();
+    intp
+}
+
+#[test]
+fn the_timers_keep_running_while_waiting() {
+    let mut intp = key_wait(Quirks::default());
+    intp.run_frame(10).unwrap();
+    assert!(intp.is_waiting_for_key());
+    assert_eq!(intp.pc(), 0x204);
+    for _ in 0..10 {
+        intp.run_frame(10).unwrap();
+    }
+    assert_eq!(intp.delay_timer(), 30 - 11);
+    assert_eq!(intp.pc(), 0x204);
+
+    intp.set_key(0xb, true);
+    intp.run_frame(10).unwrap();
+    assert!(!intp.is_waiting_for_key());
+    assert_eq!(intp.register(1), Some(0xb));
+    assert_eq!(intp.pc(), 0x206);
+}
+
+#[test]
+fn keys_held_before_the_wait_do_not_count() {
+    let mut intp = key_wait(Quirks::default());
+    intp.set_key(0x3, true);
+    intp.run_frame(10).unwrap();
+    intp.run_frame(10).unwrap();
+    assert!(intp.is_waiting_for_key());
+
+    // released and pressed again, it does
+    intp.set_key(0x3, false);
+    intp.run_frame(10).unwrap();
+    intp.set_key(0x3, true);
+    intp.run_frame(10).unwrap();
+    assert_eq!(intp.register(1), Some(0x3));
+}
+
+#[test]
+fn the_vip_completes_on_release() {
+    let mut intp = key_wait(Quirks::cosmac_vip());
+    intp.run_frame(10).unwrap();
+    intp.set_key(0x7, true);
+    intp.run_frame(10).unwrap();
+    assert!(intp.is_waiting_for_key());
+
+    intp.set_key(0x7, false);
+    intp.run_frame(10).unwrap();
+    assert!(!intp.is_waiting_for_key());
+    assert_eq!(intp.register(1), Some(0x7));
+}
+
+#[test]
+fn save_states_keep_the_wait() {
+    let mut intp = key_wait(Quirks::cosmac_vip());
+    intp.run_frame(10).unwrap();
+    intp.set_key(0x7, true);
+    intp.run_frame(10).unwrap();
+    let state = intp.save_state();
+
+    let mut restored = key_wait(Quirks::default());
+    restored.load_state(&state).unwrap();
+    assert!(restored.is_waiting_for_key());
+    assert!(restored.quirks().key_wait_release);
+    restored.set_key(0x7, false);
+    restored.run_frame(10).unwrap();
+    assert_eq!(restored.register(1), Some(0x7));
+}
@@ -0,0 +1,103 @@
+extern crate pschip8;
+
+use pschip8::{Chip8Error, Keymap, KeymapConfig};
+
+const CONFIG: &str = "
+# for every program
+keymap = hex
+5 = W, Up
+
+[Breakout.ch8]
+keymap = cosmac
+4 = Q, Left, Keypad 4
+6 =
+
+[Picture.ch8]
+A = Space
+";
+
+/// The keymap `CONFIG` gives the program
+fn keymap_for(program: &str) -> Keymap {
+    let config = KeymapConfig::parse(CONFIG, program).unwrap();
+    let mut keymap = Keymap::preset(config.preset().unwrap_or("cosmac")).unwrap();
+    config.apply(&mut keymap);
+    keymap
+}
+
+fn names(host_keys: &[&str]) -> Vec<String> {
+    host_keys.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn lines_before_the_first_section_apply_to_every_program() {
+    let keymap = keymap_for("Other.ch8");
+    let mut expected = Keymap::hex();
+    expected.set_host_keys(5, names(&["W", "Up"]));
+    assert_eq!(keymap, expected);
+}
+
+#[test]
+fn sections_apply_only_to_their_program() {
+    let keymap = keymap_for("Breakout.ch8");
+    assert_eq!(KeymapConfig::parse(CONFIG, "Breakout.ch8").unwrap().preset(), Some("cosmac"));
+    assert_eq!(keymap.host_keys(5), &names(&["W", "Up"])[..]);
+    assert_eq!(keymap.host_keys(4), &names(&["Q", "Left", "Keypad 4"])[..]);
+    // the Picture section is left out
+    assert_eq!(keymap.host_keys(0xa), Keymap::cosmac().host_keys(0xa));
+
+    let keymap = keymap_for("Picture.ch8");
+    assert_eq!(keymap.host_keys(0xa), &names(&["Space"])[..]);
+    assert_eq!(keymap.host_keys(4), Keymap::hex().host_keys(4));
+}
+
+#[test]
+fn several_host_keys_press_one_hex_key() {
+    let keymap = keymap_for("Breakout.ch8");
+    for host_key in &["Q", "Left", "Keypad 4"] {
+        assert_eq!(keymap.hex_keys(host_key), vec![4]);
+    }
+}
+
+#[test]
+fn an_empty_list_unmaps_the_key() {
+    let keymap = keymap_for("Breakout.ch8");
+    assert!(keymap.host_keys(6).is_empty());
+    assert!(keymap.hex_keys("E").is_empty());
+    assert!(keymap.hex_keys("Pad Right").is_empty());
+}
+
+#[test]
+fn lookups_ignore_case() {
+    let keymap = Keymap::cosmac();
+    assert_eq!(keymap.hex_keys("q"), vec![4]);
+    assert_eq!(keymap.hex_keys("Q"), vec![4]);
+    assert_eq!(keymap.hex_keys("pad a"), vec![5]);
+    assert!(keymap.hex_keys("Nope").is_empty());
+
+    // a host key can press several hex keys
+    let mut keymap = Keymap::cosmac();
+    keymap.set_host_keys(0, names(&["space"]));
+    keymap.set_host_keys(1, names(&["SPACE", "1"]));
+    assert_eq!(keymap.hex_keys("Space"), vec![0, 1]);
+}
+
+#[test]
+fn bad_lines_report_their_line_number() {
+    let cases = [
+        ("5 = W\nnonsense", 2),
+        ("\n\n[Breakout.ch8\n", 3),
+        ("# comment\nkeymap = dvorak", 2),
+        ("G = W", 1),
+        ("10 = W", 1),
+        // sections of other programs are checked too
+        ("[Other.ch8]\n\n5 W", 3),
+    ];
+    for &(text, line) in &cases {
+        match KeymapConfig::parse(text, "Breakout.ch8") {
+            Err(Chip8Error::InvalidKeymap(reason)) => {
+                assert!(reason.starts_with(&format!("line {}:", line)), "{:?}: {}", text, reason);
+            },
+            other => panic!("{:?}: expected an invalid keymap, got {:?}", text, other),
+        }
+    }
+}
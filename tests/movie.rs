@@ -0,0 +1,79 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::backend::{NullDisplay, NullAudio, NullInput, ScriptedInput};
+use pschip8::{Chip8Error, Movie, Runner};
+
+/// Records 300 frames of Breakout with the paddle moving both ways
+fn record() -> (Movie, Vec<u8>) {
+    let mut intp = common::breakout();
+    let mut input = ScriptedInput::default();
+    for frame in 0..300 {
+        let keys = match frame {
+            30..=59 => 1 << 6,
+            90..=149 => 1 << 4,
+            _ => 0,
+        };
+        input.push_frame(keys);
+    }
+    let mut runner = Runner::new(NullDisplay, NullAudio, input);
+    runner.record_movie(&mut intp).unwrap();
+    assert_eq!(runner.run_frames(&mut intp, 300).unwrap(), 300);
+    (runner.take_movie().unwrap(), intp.save_state())
+}
+
+#[test]
+fn playback_repeats_the_recording() {
+    let (movie, state) = record();
+    assert_eq!(movie.frames().len(), 300);
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+
+    // the settings come from the movie, not from the interpreter
+    let mut intp = common::breakout();
+    intp.set_seed(movie.seed.wrapping_add(1));
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    runner.play_movie(&mut intp, movie, true).unwrap();
+    // the run stops at the end of the movie
+    assert_eq!(runner.run_frames(&mut intp, 1000).unwrap(), 300);
+    assert_eq!(runner.movie_desync(), None);
+    assert_eq!(intp.save_state(), state);
+}
+
+#[test]
+fn desyncs_are_detected() {
+    let (movie, _) = record();
+    let mut data = movie.to_bytes();
+    // hold key 6 from frame 200 on, the frames are the last 300 records of
+    // 11 bytes with the keys first
+    let frames = data.len() - 300 * 11;
+    for frame in 200..300 {
+        data[frames + frame * 11 + 1] |= 1 << 6;
+    }
+    let movie = Movie::from_bytes(&data).unwrap();
+
+    let mut intp = common::breakout();
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    runner.play_movie(&mut intp, movie.clone(), false).unwrap();
+    assert_eq!(runner.run_frames(&mut intp, 1000).unwrap(), 300);
+    let frame = runner.movie_desync().expect("the movie should desync");
+    assert!(frame >= 200);
+
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    runner.play_movie(&mut intp, movie, true).unwrap();
+    match runner.run_frames(&mut intp, 1000) {
+        Err(Chip8Error::MovieDesync { frame: stopped }) => assert_eq!(stopped, frame),
+        other => panic!("expected a desync, got {:?}", other),
+    }
+}
+
+#[test]
+fn movies_belong_to_one_program() {
+    let (movie, _) = record();
+    let mut intp = common::load(&[0x1200]);
+    let mut runner = Runner::new(NullDisplay, NullAudio, NullInput);
+    match runner.play_movie(&mut intp, movie, true) {
+        Err(Chip8Error::InvalidMovie(_)) => {},
+        other => panic!("expected an invalid movie, got {:?}", other),
+    }
+}
@@ -0,0 +1,125 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::{Access, Chip8Error, Interpreter, Pause, Quirks, Watchpoint};
+
+#[test]
+fn load_store_wraps_i_at_the_end_of_memory() {
+    // V0 = 0x42, I = 0xffff, store V0
+    let mut intp = common::load(&[0x6042, 0xf000, 0xffff, 0xf055]);
+    intp.set_quirks(Quirks::xochip()).unwrap();
+    intp.step().unwrap();
+    intp.step().unwrap();
+    assert_eq!(intp.index(), 0xffff);
+    intp.step().unwrap();
+    assert_eq!(intp.memory()[0xffff], 0x42);
+    assert_eq!(intp.index(), 0);
+}
+
+#[test]
+fn load_store_wraps_around_the_end_of_memory() {
+    // V0 - V3 = 1 - 4, I = 0xfffe, store V0 - V3, load them into V4 - V7
+    let mut intp = common::load(&[0x6001, 0x6102, 0x6203, 0x6304, 0xf000, 0xfffe, 0xf355,
+                                  0xf000, 0xfffe, 0x5473]);
+    intp.set_quirks(Quirks::xochip()).unwrap();
+    intp.add_watchpoint(Watchpoint::writes(0, 0));
+    for _ in 0..6 {
+        intp.step().unwrap();
+    }
+    assert_eq!(intp.pause(), Some(Pause::Watchpoint { pc: 0x20c, address: 0, access: Access::Write }));
+    assert_eq!(&intp.memory()[0xfffe..], &[1, 2]);
+    assert_eq!(&intp.memory()[..2], &[3, 4]);
+    assert_eq!(intp.index(), 2);
+
+    intp.step().unwrap();
+    intp.step().unwrap();
+    let registers: Vec<u8> = (4..8).map(|x| intp.register(x).unwrap()).collect();
+    assert_eq!(registers, vec![1, 2, 3, 4]);
+
+    // the 4 KiB memory doesn't wrap
+    let mut intp = common::load(&[0xaffe, 0xf355]);
+    intp.step().unwrap();
+    match intp.step() {
+        Err(Chip8Error::MemoryOutOfBounds { pc: 0x202, address: 0x1001 }) => {},
+        other => panic!("expected an out of bounds access, got {:?}", other),
+    }
+}
+
+#[test]
+fn shrinking_memory_keeps_programs_that_no_longer_fit() {
+    let mut intp = Interpreter::new();
+    intp.set_quirks(Quirks::xochip()).unwrap();
+    intp.load_from_bytes(&[0x1200; 3000]).unwrap();
+    match intp.set_quirks(Quirks::default()) {
+        Err(Chip8Error::RomTooLarge { size: 6000, .. }) => {},
+        other => panic!("expected the program to be too large, got {:?}", other),
+    }
+    assert_eq!(intp.program().len(), 6000);
+    assert!(intp.quirks().large_memory);
+
+    // a program that fits can switch
+    intp.load_from_bytes(&[0x1200]).unwrap();
+    intp.set_quirks(Quirks::default()).unwrap();
+    assert_eq!(intp.memory().len(), 4096);
+}
+
+/// Draws the 0 digit in the high resolution mode twice at the top left, then
+/// once across the bottom edge. Returns VF after the second and third draw.
+fn hires_collisions(quirks: Quirks) -> (u8, u8) {
+    let mut intp = common::load(&[0x00ff, 0x6000, 0xf029, 0xd005, 0xd005, 0x613e, 0xd015]);
+    intp.set_quirks(quirks).unwrap();
+    for _ in 0..5 {
+        intp.step().unwrap();
+    }
+    let erased = intp.register(0xf).unwrap();
+    intp.step().unwrap();
+    intp.step().unwrap();
+    (erased, intp.register(0xf).unwrap())
+}
+
+#[test]
+fn superchip_counts_colliding_and_clipped_rows() {
+    assert_eq!(hires_collisions(Quirks::superchip()), (5, 3));
+    assert_eq!(hires_collisions(Quirks::chip48()), (1, 0));
+}
+
+/// Draws the 0 digit at (1, 1) in the low resolution mode, then switches to
+/// the high resolution mode
+fn switch_resolution(quirks: Quirks) -> Interpreter {
+    let mut intp = common::load(&[0x6001, 0x6100, 0xf129, 0xd005, 0x00ff, 0x00fe]);
+    intp.set_quirks(quirks).unwrap();
+    for _ in 0..5 {
+        intp.step().unwrap();
+    }
+    intp
+}
+
+#[test]
+fn superchip_keeps_the_picture_when_switching_resolution() {
+    let mut intp = switch_resolution(Quirks::superchip());
+    let framebuffer = intp.framebuffer();
+    assert!(framebuffer.is_hires());
+    // the top row of the 0 is 0xf0, four lores pixels from (1, 1) on
+    for x in 0..12 {
+        assert_eq!(framebuffer.pixel(x, 2), (2..10).contains(&x), "pixel ({}, 2)", x);
+        assert_eq!(framebuffer.pixel(x, 3), (2..10).contains(&x), "pixel ({}, 3)", x);
+    }
+    assert!(!framebuffer.pixel(2, 1));
+
+    // and back, where each 2x2 square becomes one pixel again
+    intp.step().unwrap();
+    assert!(!intp.framebuffer().is_hires());
+    let mut expected = common::load(&[0x6001, 0x6100, 0xf129, 0xd005]);
+    for _ in 0..4 {
+        expected.step().unwrap();
+    }
+    assert_eq!(intp.framebuffer().pixels(), expected.framebuffer().pixels());
+}
+
+#[test]
+fn xochip_clears_the_screen_when_switching_resolution() {
+    let intp = switch_resolution(Quirks::xochip());
+    assert!(intp.framebuffer().is_hires());
+    assert!(intp.framebuffer().pixels().iter().all(|pixel| *pixel == 0));
+}
@@ -0,0 +1,50 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::backend::{Command, NullAudio, NullDisplay, ScriptedInput};
+use pschip8::{RewindBuffer, Runner};
+
+#[test]
+fn states_come_back_newest_first_up_to_the_capacity() {
+    let states: Vec<Vec<u8>> = (0..8u8)
+        .map(|n| (0..40 + n as usize * 3).map(|idx| if idx % 7 == 0 { n } else { idx as u8 }).collect())
+        .collect();
+    let mut buffer = RewindBuffer::new(5);
+    for state in &states {
+        buffer.push(state.clone());
+    }
+    assert_eq!(buffer.len(), 5);
+    for state in states.iter().rev().take(5) {
+        assert_eq!(buffer.pop().as_ref(), Some(state));
+    }
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.pop(), None);
+}
+
+#[test]
+fn rewinding_returns_to_earlier_frames() {
+    // counts V0 up, every frame ends in a different state
+    let mut intp = common::load(&[0x7001, 0x1200]);
+    let mut runner = Runner::new(NullDisplay, NullAudio, ScriptedInput::default());
+    runner.set_rewind_seconds(1);
+    let mut states = Vec::new();
+    for _ in 0..90 {
+        states.push(intp.save_state());
+        runner.run_frames(&mut intp, 1).unwrap();
+    }
+
+    // one second back at most, then it stays at the oldest state
+    for back in 1..=70 {
+        runner.input_mut().push_command(Command::Rewind);
+        runner.run_frames(&mut intp, 1).unwrap();
+        let expected = &states[90 - ::std::cmp::min(back, 60)];
+        assert_eq!(&intp.save_state(), expected, "{} frames back", back);
+    }
+}
+
+#[test]
+fn huge_rewind_lengths_do_not_overflow() {
+    let mut runner = Runner::new(NullDisplay, NullAudio, ScriptedInput::default());
+    runner.set_rewind_seconds(u32::MAX);
+}
@@ -0,0 +1,154 @@
+extern crate pschip8;
+
+use pschip8::{write_pbm, write_png, Framebuffer, PALETTE};
+
+/// A framebuffer with a pixel lit in the top left corner and one, on the
+/// second plane, in the bottom right corner
+fn corners(hires: bool) -> Framebuffer {
+    let mut framebuffer = Framebuffer::default();
+    framebuffer.set_hires(hires);
+    framebuffer.draw(0, 0, &[0x80], false);
+    let (x, y) = (framebuffer.width() - 1, framebuffer.height() - 1);
+    framebuffer.select_planes(0x2);
+    framebuffer.draw(x, y, &[0x80], false);
+    framebuffer
+}
+
+fn be32(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).fold(0, |value, byte| value << 8 | *byte as u32)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
+
+/// Splits a PNG into its chunks, checking the signature and every CRC
+fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        let len = be32(rest) as usize;
+        let body = &rest[4..8 + len];
+        assert_eq!(be32(&rest[8 + len..]), crc32(body), "CRC of {:?}", String::from_utf8_lossy(&body[..4]));
+        chunks.push(([body[0], body[1], body[2], body[3]], body[4..].to_vec()));
+        rest = &rest[12 + len..];
+    }
+    chunks
+}
+
+/// Reads back the data of a zlib stream of stored deflate blocks, checking
+/// its Adler-32
+fn unstore(zlib: &[u8]) -> Vec<u8> {
+    assert_eq!(&zlib[..2], &[0x78, 0x01]);
+    let mut data = Vec::new();
+    let mut at = 2;
+    loop {
+        let last = zlib[at] & 1 != 0;
+        assert_eq!(zlib[at] & 0x6, 0, "only stored blocks are written");
+        let len = zlib[at + 1] as usize | (zlib[at + 2] as usize) << 8;
+        let nlen = zlib[at + 3] as usize | (zlib[at + 4] as usize) << 8;
+        assert_eq!(len ^ nlen, 0xffff);
+        data.extend_from_slice(&zlib[at + 5..at + 5 + len]);
+        at += 5 + len;
+        if last {
+            break;
+        }
+    }
+    assert_eq!(be32(&zlib[at..]), adler32(&data));
+    assert_eq!(zlib.len(), at + 4);
+    data
+}
+
+#[test]
+fn the_checksums_of_the_test_match_known_values() {
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+}
+
+#[test]
+fn pbm_packs_the_scaled_rows_in_bits() {
+    let mut pbm = Vec::new();
+    write_pbm(&corners(false), 1, &mut pbm).unwrap();
+    let header = b"P4\n64 32\n";
+    assert_eq!(&pbm[..header.len()], header);
+    let mut bits = vec![0u8; 8 * 32];
+    bits[0] = 0x80;
+    bits[8 * 32 - 1] = 0x01;
+    assert_eq!(&pbm[header.len()..], &bits[..]);
+
+    // 384 pixels wide, every row is 48 bytes
+    let mut pbm = Vec::new();
+    write_pbm(&corners(true), 3, &mut pbm).unwrap();
+    let header = b"P4\n384 192\n";
+    assert_eq!(&pbm[..header.len()], header);
+    let mut bits = vec![0u8; 48 * 192];
+    for row in 0..3 {
+        bits[row * 48] = 0xe0;
+        bits[(191 - row) * 48 + 47] = 0x07;
+    }
+    assert_eq!(&pbm[header.len()..], &bits[..]);
+}
+
+#[test]
+fn a_scale_of_zero_draws_at_one() {
+    let mut pbm = Vec::new();
+    write_pbm(&corners(false), 0, &mut pbm).unwrap();
+    let mut expected = Vec::new();
+    write_pbm(&corners(false), 1, &mut expected).unwrap();
+    assert_eq!(pbm, expected);
+}
+
+fn check_png(framebuffer: &Framebuffer, scale: usize) {
+    let mut png = Vec::new();
+    write_png(framebuffer, scale as u32, &mut png).unwrap();
+    let chunks = chunks(&png);
+    let kinds: Vec<&[u8]> = chunks.iter().map(|chunk| &chunk.0[..]).collect();
+    assert_eq!(kinds, vec![&b"IHDR"[..], b"PLTE", b"IDAT", b"IEND"]);
+
+    let width = framebuffer.width() as usize * scale;
+    let height = framebuffer.height() as usize * scale;
+    let header = &chunks[0].1;
+    assert_eq!(header.len(), 13);
+    assert_eq!(be32(&header[0..]) as usize, width);
+    assert_eq!(be32(&header[4..]) as usize, height);
+    assert_eq!(&header[8..], &[8, 3, 0, 0, 0]);
+
+    let palette: Vec<u8> = PALETTE.iter().flat_map(|&(r, g, b)| vec![r, g, b]).collect();
+    assert_eq!(chunks[1].1, palette);
+    assert!(chunks[3].1.is_empty());
+
+    let image = unstore(&chunks[2].1);
+    assert_eq!(image.len(), height * (width + 1));
+    for (y, row) in image.chunks(width + 1).enumerate() {
+        assert_eq!(row[0], 0, "filter of row {}", y);
+        for (x, pixel) in row[1..].iter().enumerate() {
+            let color = framebuffer.color((x / scale) as u8, (y / scale) as u8);
+            assert_eq!(*pixel, color, "pixel ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn png_holds_the_scaled_pixels() {
+    check_png(&corners(false), 1);
+    check_png(&corners(false), 4);
+    check_png(&corners(true), 1);
+    // 513 bytes a row for 256 rows take more than one deflate block
+    check_png(&corners(true), 4);
+}
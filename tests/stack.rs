@@ -0,0 +1,75 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::{Chip8Error, StackPolicy, STACK_DEPTH};
+
+#[test]
+fn calls_past_the_top_of_the_stack_trap() {
+    // calls itself until the stack is full
+    let mut intp = common::load(&[0x2200]);
+    match intp.run_frame(100) {
+        Err(Chip8Error::StackOverflow { pc, stack }) => {
+            assert_eq!(pc, 0x200);
+            assert_eq!(stack, vec![0x202; STACK_DEPTH as usize]);
+        },
+        other => panic!("expected a stack overflow, got {:?}", other),
+    }
+    assert_eq!(intp.call_stack().len(), STACK_DEPTH as usize);
+}
+
+#[test]
+fn returns_with_an_empty_stack_trap() {
+    // one call and two returns
+    let mut intp = common::load(&[0x2204, 0x00ee, 0x00ee]);
+    match intp.run_frame(100) {
+        Err(Chip8Error::StackUnderflow { pc, stack }) => {
+            assert_eq!(pc, 0x202);
+            assert!(stack.is_empty());
+        },
+        other => panic!("expected a stack underflow, got {:?}", other),
+    }
+}
+
+#[test]
+fn the_stack_depth_can_be_changed() {
+    let mut intp = common::load(&[0x2202, 0x2204, 0x2206]);
+    intp.set_stack_depth(2);
+    let error = intp.run_frame(100).unwrap_err();
+    match error {
+        Chip8Error::StackOverflow { pc, ref stack } => {
+            assert_eq!(pc, 0x204);
+            assert_eq!(stack, &[0x202, 0x204]);
+        },
+        ref other => panic!("expected a stack overflow, got {:?}", other),
+    }
+    assert_eq!(error.to_string(), "stack overflow at 0x204, call stack: [0x202, 0x204]");
+
+    // a depth of 0 still holds one address
+    let mut intp = common::load(&[0x2202, 0x2204]);
+    intp.set_stack_depth(0);
+    assert!(intp.step().is_ok());
+    assert!(intp.step().is_err());
+}
+
+#[test]
+fn the_wrap_policy_wraps_the_stack_pointer() {
+    // three nested calls on a stack of two, then returns
+    let mut intp = common::load(&[0x2202, 0x2204, 0x2206, 0x00ee]);
+    intp.set_stack_depth(2);
+    intp.set_stack_policy(StackPolicy::Wrap);
+    for _ in 0..3 {
+        intp.step().unwrap();
+    }
+    // the third call overwrote the oldest entry
+    assert_eq!(intp.pc(), 0x206);
+    assert_eq!(intp.call_stack(), &[0x206]);
+
+    intp.step().unwrap();
+    assert_eq!(intp.pc(), 0x206);
+    assert!(intp.call_stack().is_empty());
+    // returning from the empty stack takes the entry at the other end
+    intp.step().unwrap();
+    assert_eq!(intp.pc(), 0x204);
+    assert_eq!(intp.call_stack(), &[0x206]);
+}
@@ -0,0 +1,74 @@
+extern crate pschip8;
+
+mod common;
+
+use std::path::PathBuf;
+use pschip8::backend::{Command, NullAudio, NullDisplay, ScriptedInput};
+use pschip8::{Chip8Error, Interpreter, Quirks, Runner};
+
+fn breakout() -> Interpreter {
+    let mut intp = common::breakout();
+    intp.set_quirks(Quirks::cosmac_vip()).unwrap();
+    intp.set_seed(7);
+    intp
+}
+
+fn run(intp: &mut Interpreter, frames: usize) {
+    for frame in 0..frames {
+        intp.set_key(6, frame % 40 < 20);
+        intp.run_frame(10).unwrap();
+    }
+}
+
+#[test]
+fn a_loaded_state_runs_on_like_the_saved_machine() {
+    let mut intp = breakout();
+    run(&mut intp, 120);
+    let saved = intp.save_state();
+    run(&mut intp, 200);
+    let later = intp.save_state();
+
+    // into a fresh interpreter with other quirks and seed
+    let mut restored = Interpreter::new();
+    restored.load_program_from_file(&common::program_path(common::BREAKOUT)).unwrap();
+    restored.load_state(&saved).unwrap();
+    assert_eq!(restored.save_state(), saved);
+    run(&mut restored, 200);
+    assert_eq!(restored.save_state(), later);
+    assert_eq!(restored.framebuffer().pixels(), intp.framebuffer().pixels());
+}
+
+#[test]
+fn bad_states_leave_the_machine_alone() {
+    let mut intp = breakout();
+    run(&mut intp, 60);
+    let saved = intp.save_state();
+    run(&mut intp, 60);
+    let current = intp.save_state();
+
+    let truncated = &saved[..saved.len() - 1];
+    match intp.load_state(truncated) {
+        Err(Chip8Error::InvalidState(_)) => {},
+        other => panic!("expected an invalid state, got {:?}", other),
+    }
+    let mut other = common::load(&[0x1200]);
+    assert!(other.load_state(&saved).is_err());
+    assert_eq!(intp.save_state(), current);
+}
+
+#[test]
+fn slots_that_fail_are_reported_and_the_run_goes_on() {
+    let mut intp = breakout();
+    let mut runner = Runner::new(NullDisplay, NullAudio, ScriptedInput::default());
+    runner.set_state_path(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("no such directory/breakout.ch8"));
+    runner.input_mut().push_command(Command::SaveState(3));
+    assert_eq!(runner.run_frames(&mut intp, 2).unwrap(), 2);
+
+    let errors = runner.take_errors();
+    match errors.as_slice() {
+        [Chip8Error::StateSlot { slot: 3, error }] => assert!(matches!(**error, Chip8Error::Io(_))),
+        other => panic!("expected slot 3 to fail, got {:?}", other),
+    }
+    assert!(errors[0].to_string().starts_with("save state slot 3: "));
+    assert!(runner.take_errors().is_empty());
+}
@@ -0,0 +1,21 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::backend::{NullAudio, NullInput, RecordingDisplay};
+use pschip8::Runner;
+
+#[test]
+fn only_frames_that_change_the_screen_are_presented() {
+    // draws the 0 digit, waits 10 frames, clears the screen, then loops
+    let mut intp = common::load(&[0x6000, 0xf029, 0xd005, 0x610a, 0xf115, 0xf107, 0x3100, 0x120a,
+                                0x00e0, 0x1212]);
+    let mut runner = Runner::new(RecordingDisplay::default(), NullAudio, NullInput);
+    assert_eq!(runner.run_frames(&mut intp, 60).unwrap(), 60);
+
+    let stats = runner.stats();
+    assert_eq!(stats.frames(), 60);
+    assert_eq!(stats.presented(), 2);
+    assert_eq!(runner.display().frames().len(), 2);
+    assert!(stats.slowest_frame() >= stats.average_run());
+}
@@ -0,0 +1,84 @@
+extern crate pschip8;
+
+mod common;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use pschip8::{BinaryTrace, Chip8Error, FilteredTrace, RingTrace, TraceEntry, TraceFilter, TraceReader};
+
+/// A writer whose bytes stay readable after the tracer took it
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs the program for `steps` instructions, keeping the entries of every
+/// instruction and the binary trace of those the filter passes
+fn trace(program: &[u16], steps: usize, filter: TraceFilter) -> (Vec<TraceEntry>, Vec<u8>) {
+    let buffer = SharedBuffer::default();
+    let ring = RingTrace::new(steps);
+    let mut intp = common::load(program);
+    intp.set_tracer(Some(Box::new(FilteredTrace::new(filter, BinaryTrace::new(buffer.clone()).unwrap()))));
+    for _ in 0..steps {
+        intp.step().unwrap();
+    }
+    let mut all = common::load(program);
+    all.set_tracer(Some(Box::new(ring.clone())));
+    for _ in 0..steps {
+        all.step().unwrap();
+    }
+    let data = buffer.0.borrow().clone();
+    (ring.entries(), data)
+}
+
+#[test]
+fn binary_traces_read_back() {
+    let program = [0x6005, 0x7001, 0xa300, 0xf000, 0x0123, 0x8014, 0x1200];
+    let (entries, data) = trace(&program, 12, TraceFilter::default());
+    let read: Vec<TraceEntry> = TraceReader::new(&data[..]).unwrap().map(|entry| entry.unwrap()).collect();
+    assert_eq!(read, entries);
+}
+
+#[test]
+fn filtered_traces_keep_the_registers_skipped_instructions_set() {
+    // only the 7xkk adds are traced, V1 = 7 in between is not
+    let filter = TraceFilter { classes: 1 << 0x7, ..TraceFilter::default() };
+    let (entries, data) = trace(&[0x7001, 0x6107, 0x7001], 3, filter);
+    let read: Vec<TraceEntry> = TraceReader::new(&data[..]).unwrap().map(|entry| entry.unwrap()).collect();
+    let expected: Vec<TraceEntry> = entries.into_iter().filter(|entry| filter.matches(entry)).collect();
+    assert_eq!(read, expected);
+    assert_eq!(read[1].before[1], 7);
+}
+
+#[test]
+fn the_failing_instruction_is_traced() {
+    let ring = RingTrace::new(4);
+    // return with nothing on the stack
+    let mut intp = common::load(&[0x6001, 0x00ee]);
+    intp.set_tracer(Some(Box::new(ring.clone())));
+    intp.step().unwrap();
+    assert!(intp.step().is_err());
+    let entries = ring.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[1].pc, entries[1].opcode), (0x202, 0x00ee));
+
+    // undecodable opcodes too
+    let ring = RingTrace::new(4);
+    let mut intp = common::load(&[0x5001]);
+    intp.set_tracer(Some(Box::new(ring.clone())));
+    match intp.step() {
+        Err(Chip8Error::InvalidOpcode { address: 0x200, opcode: 0x5001 }) => {},
+        other => panic!("expected an invalid opcode, got {:?}", other),
+    }
+    assert_eq!(ring.entries()[0].opcode, 0x5001);
+}
@@ -0,0 +1,140 @@
+extern crate pschip8;
+
+mod common;
+
+use pschip8::{Access, Comparison, Condition, Interpreter, Pause, Quirks, Register, Watchpoint};
+
+/// Runs a frame, which should pause on the watchpoint at the instruction at
+/// `pc` touching `address`
+fn assert_watch(intp: &mut Interpreter, pc: u16, address: u16, access: Access) {
+    intp.run_frame(100).unwrap();
+    assert_eq!(intp.pause(), Some(Pause::Watchpoint { pc, address, access }));
+    // the instruction ran
+    assert_eq!(intp.pc(), pc + 2);
+}
+
+#[test]
+fn watchpoints_parse_what_they_display() {
+    let watchpoints = [
+        Watchpoint::writes(0x300, 0x300),
+        Watchpoint::reads(0x300, 0x30f),
+        Watchpoint::fetches(0x200, 0x2ff),
+        Watchpoint { read: true, ..Watchpoint::writes(0xffe, 0xfff) },
+    ];
+    for watchpoint in &watchpoints {
+        assert_eq!(watchpoint.to_string().parse(), Ok(*watchpoint));
+    }
+    assert_eq!("READ/fetch 16-0x20".parse(),
+               Ok(Watchpoint { fetch: true, ..Watchpoint::reads(16, 0x20) }));
+
+    for bad in &["", "write", "0x300", "write 0x300 0x301", "erase 0x300", "write/ 0x300",
+                 "write 0x10000", "write 0x300-", "write 0x301-0x300", "write zero"] {
+        assert!(bad.parse::<Watchpoint>().is_err(), "'{}' parsed", bad);
+    }
+}
+
+#[test]
+fn conditions_parse_with_or_without_spaces() {
+    assert_eq!("VF == 1".parse(),
+               Ok(Condition { register: Register::V(0xf), comparison: Comparison::Eq, value: 1 }));
+    assert_eq!("i>0xE00".parse(),
+               Ok(Condition { register: Register::I, comparison: Comparison::Gt, value: 0xe00 }));
+    assert_eq!(" dt <= 3 ".parse(),
+               Ok(Condition { register: Register::Dt, comparison: Comparison::Le, value: 3 }));
+    assert_eq!("v0 != 0x1F".parse(),
+               Ok(Condition { register: Register::V(0), comparison: Comparison::Ne, value: 0x1f }));
+    let condition = Condition { register: Register::Pc, comparison: Comparison::Ge, value: 0x2a0 };
+    assert_eq!(condition.to_string().parse(), Ok(condition));
+
+    for bad in &["", "VF", "VF = 1", "VG == 1", "V10 == 1", "== 1", "VF ==", "VF == one", "I < 0x10000"] {
+        assert!(bad.parse::<Condition>().is_err(), "'{}' parsed", bad);
+    }
+}
+
+#[test]
+fn bcd_and_register_stores_are_writes() {
+    // I = 0x300, V0 = 0x99, BCD of V0 at I
+    let mut intp = common::load(&[0xa300, 0x6099, 0xf033, 0x1206]);
+    intp.add_watchpoint(Watchpoint::writes(0x302, 0x302));
+    assert_watch(&mut intp, 0x204, 0x302, Access::Write);
+    assert_eq!(&intp.memory()[0x300..0x303], &[1, 5, 3]);
+
+    // V0 through V2 stored at I = 0x310
+    let mut intp = common::load(&[0xa310, 0x6001, 0x6102, 0x6203, 0xf255, 0x120a]);
+    intp.add_watchpoint(Watchpoint::reads(0x300, 0x3ff));
+    intp.add_watchpoint(Watchpoint::writes(0x311, 0x320));
+    assert_watch(&mut intp, 0x208, 0x311, Access::Write);
+    assert_eq!(&intp.memory()[0x310..0x313], &[1, 2, 3]);
+}
+
+#[test]
+fn register_loads_are_reads() {
+    // V0 through V3 loaded from I = 0x20e, the last bytes of the program
+    let mut intp = common::load(&[0xa20e, 0xf365, 0x1204]);
+    intp.add_watchpoint(Watchpoint::writes(0x200, 0x2ff));
+    intp.add_watchpoint(Watchpoint::reads(0x210, 0x210));
+    assert_watch(&mut intp, 0x202, 0x210, Access::Read);
+}
+
+#[test]
+fn sprites_are_read() {
+    // I = 0x300, a 5 row sprite drawn from it
+    let mut intp = common::load(&[0xa300, 0xd015, 0x1204]);
+    intp.add_watchpoint(Watchpoint::reads(0x304, 0x310));
+    assert_watch(&mut intp, 0x202, 0x304, Access::Read);
+
+    // one row short of the watchpoint
+    let mut intp = common::load(&[0xa300, 0xd014, 0x1204]);
+    intp.add_watchpoint(Watchpoint::reads(0x304, 0x310));
+    intp.run_frame(100).unwrap();
+    assert_eq!(intp.pause(), None);
+}
+
+#[test]
+fn fetches_are_watched() {
+    let mut intp = common::load(&[0x6001, 0x6102, 0x6203, 0x1206]);
+    intp.add_watchpoint(Watchpoint::reads(0x200, 0x2ff));
+    intp.add_watchpoint(Watchpoint::fetches(0x205, 0x205));
+    // the second byte of the instruction at 0x204
+    assert_watch(&mut intp, 0x204, 0x205, Access::Fetch);
+    assert_eq!(intp.register(2), Some(3));
+
+    // the next frame carries on, the jump at 0x206 is never watched
+    intp.run_frame(3).unwrap();
+    assert_eq!(intp.pause(), None);
+}
+
+#[test]
+fn register_conditions_pause_when_they_turn_true() {
+    // V0 + V1 overflows and sets VF
+    let mut intp = common::load(&[0x60ff, 0x6101, 0x8014, 0x1206]);
+    intp.add_condition("VF == 1".parse().unwrap());
+    intp.run_frame(100).unwrap();
+    assert_eq!(intp.pause(), Some(Pause::Condition("VF == 1".parse().unwrap())));
+    assert_eq!(intp.pc(), 0x206);
+    // it stays true, so it doesn't pause again
+    intp.run_frame(100).unwrap();
+    assert_eq!(intp.pause(), None);
+
+    // I grows by 0xff with every other instruction
+    let mut intp = common::load(&[0x60ff, 0xf01e, 0x1202]);
+    intp.add_condition("I > 0xE00".parse().unwrap());
+    intp.run_frame(100).unwrap();
+    assert!(matches!(intp.pause(), Some(Pause::Condition(_))));
+    assert_eq!(intp.index(), 15 * 0xff);
+}
+
+#[test]
+fn ranges_reach_the_end_of_memory() {
+    // V0 and V1 stored in the last two bytes of the 4 KiB
+    let mut intp = common::load(&[0xaffe, 0xf155, 0x1204]);
+    intp.add_watchpoint(Watchpoint::writes(0xfff, 0xfff));
+    assert_watch(&mut intp, 0x202, 0xfff, Access::Write);
+
+    // the last byte of the XO-CHIP 64 KiB
+    let mut intp = common::load(&[0xf000, 0xffff, 0x6042, 0xf055, 0x1208]);
+    intp.set_quirks(Quirks::xochip()).unwrap();
+    intp.add_watchpoint("write 0xFF00-0xFFFF".parse().unwrap());
+    assert_watch(&mut intp, 0x206, 0xffff, Access::Write);
+    assert_eq!(intp.memory()[0xffff], 0x42);
+}